@@ -0,0 +1,80 @@
+use crate::events::{Event, Subscriber};
+
+// Kills needed for the centurion achievement
+const CENTURION_KILLS: usize = 100;
+// Level needed for the survivor achievement
+const SURVIVOR_LEVEL: u64 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Achievement {
+    /// Shoot down the first invader
+    FirstBlood,
+    /// Shoot down one of the top row invaders
+    TopGun,
+    /// Clear a level without getting hit
+    Untouchable,
+    /// Shoot down a hundred invaders in one game
+    Centurion,
+    /// Clear the fifth level
+    Survivor,
+}
+
+impl Achievement {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Achievement::FirstBlood => "First Blood",
+            Achievement::TopGun => "Top Gun",
+            Achievement::Untouchable => "Untouchable",
+            Achievement::Centurion => "Centurion",
+            Achievement::Survivor => "Survivor",
+        }
+    }
+}
+
+/// Keeps track of the achievements unlocked during a game
+#[derive(Default)]
+pub struct Achievements {
+    pub unlocked: Vec<Achievement>,
+    kills: usize,
+    hit_this_level: bool,
+}
+
+impl Achievements {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn unlock(&mut self, achievement: Achievement) {
+        if !self.unlocked.contains(&achievement) {
+            self.unlocked.push(achievement);
+        }
+    }
+}
+
+impl Subscriber for Achievements {
+    fn notify(&mut self, event: &Event) {
+        match *event {
+            Event::InvaderKilled { kind, .. } => {
+                self.kills += 1;
+                self.unlock(Achievement::FirstBlood);
+                if kind == 0 {
+                    self.unlock(Achievement::TopGun);
+                }
+                if self.kills >= CENTURION_KILLS {
+                    self.unlock(Achievement::Centurion);
+                }
+            }
+            Event::PlayerHit { .. } => self.hit_this_level = true,
+            Event::LevelCleared { level } => {
+                if !self.hit_this_level {
+                    self.unlock(Achievement::Untouchable);
+                }
+                if level >= SURVIVOR_LEVEL {
+                    self.unlock(Achievement::Survivor);
+                }
+                self.hit_this_level = false;
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,33 +1,98 @@
-use std::{cmp::max, time::Duration};
+use std::{cmp::max, sync::Arc, time::Duration};
 
-use rand::Rng;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use rusty_time::timer::Timer;
 
 use crate::{
-    frame::{Drawable, Frame, Transform, Updatable},
+    frame::{self, Drawable, Frame, Transform, Updatable},
     invader::Invader,
+    pixels::{Collision, Pixels},
     shot::Shot,
+    sprites::{Sprite, SpriteSheet},
     NUM_COLS, NUM_ROWS,
 };
 
+// Number of invader columns in the starting formation
+const NUM_FORMATION_COLS: usize = 9;
+// Reload time between shots when a player commands the army
+const COMMAND_RELOAD_MS: u64 = 1000;
+// How much one nudge changes the march timing
+const NUDGE_STEP_MS: i64 = 50;
+
+/// Difficulty knobs for the army, the defaults are the classic game
+#[derive(Clone, Copy, Debug)]
+pub struct Tuning {
+    /// Milliseconds between march steps, before `march_level_ms` comes off
+    /// for every level. Levels start at 1.
+    pub march_ms: i64,
+    /// How much faster each level marches
+    pub march_level_ms: i64,
+    /// Marching never gets faster than this
+    pub march_min_ms: i64,
+    /// Range of the random pause between invader shots
+    pub fire_min_ms: u64,
+    pub fire_max_ms: u64,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            march_ms: 1100,
+            march_level_ms: 100,
+            march_min_ms: 200,
+            fire_min_ms: 500,
+            fire_max_ms: 8000,
+        }
+    }
+}
+
+/// Orders a second player can give the army in versus mode
+pub enum Command {
+    NextColumn,
+    PrevColumn,
+    Fire,
+    Faster,
+    Slower,
+}
+
 pub struct Army {
     pub invaders: Vec<Invader>,
     pub shots: Vec<Shot>,
     direction: isize,
     move_timer: Timer,
     shot_timer: Timer,
+    commanded: bool,
+    selected_column: usize,
+    march_nudge: i64,
+    rng: StdRng,
+    tuning: Tuning,
+    marched: bool,
+    shot_sprite: Arc<Sprite>,
 }
 
 impl Army {
     pub fn new(level: u64) -> Self {
+        Self::with_seed(level, rand::random())
+    }
+
+    /// An army whose firing pattern is the same every time for a given seed
+    pub fn with_seed(level: u64, seed: u64) -> Self {
+        Self::with_tuning(level, seed, Tuning::default(), &SpriteSheet::builtin())
+    }
+
+    /// Invaders and their shots look, and are as big, as `sheet` says
+    pub fn with_tuning(level: u64, seed: u64, tuning: Tuning, sheet: &SpriteSheet) -> Self {
         let mut invaders = Vec::new();
-        for i in 0..9 {
+        for i in 0..NUM_FORMATION_COLS {
             for j in 0..5 {
-                invaders.push(Invader::new(9 + i * 7, 2 + j * 3));
+                invaders.push(Invader::new(9 + i * 7, 2 + j * 3, sheet));
             }
         }
 
-        let move_timer_time = max(200, 1100 - level as i64 * 100) as u64;
+        let move_timer_time = max(
+            tuning.march_min_ms,
+            tuning.march_ms - level as i64 * tuning.march_level_ms,
+        ) as u64;
 
         Self {
             invaders,
@@ -35,37 +100,161 @@ impl Army {
             direction: 1,
             move_timer: Timer::from_millis(move_timer_time),
             shot_timer: Timer::from_millis(4000),
+            commanded: false,
+            selected_column: NUM_FORMATION_COLS / 2,
+            march_nudge: 0,
+            rng: StdRng::seed_from_u64(seed),
+            tuning,
+            marched: false,
+            shot_sprite: sheet.get("shot_down"),
         }
     }
 
-    pub fn move_army(&mut self) {
-        if self.invaders.is_empty() {
+    /// Hand the choice of shooter and the march timing to a player. The
+    /// random shooter from `get_shooter` is no longer used.
+    pub fn set_commanded(&mut self, commanded: bool) {
+        self.commanded = commanded;
+        if commanded {
+            self.shot_timer = Timer::from_millis(COMMAND_RELOAD_MS);
+        }
+    }
+
+    pub fn tuning(&self) -> Tuning {
+        self.tuning
+    }
+
+    /// Whether the army took a step during the last update
+    pub fn marched(&self) -> bool {
+        self.marched
+    }
+
+    pub fn commanded(&self) -> bool {
+        self.commanded
+    }
+
+    /// Marks the column the commander is aiming with, under its shooter.
+    /// Renderers that draw the army themselves still put this on top.
+    pub fn draw_aim(&self, frame: &mut Frame) {
+        if !self.commanded {
             return;
         }
+        if let Some(invader) = self.column_shooter(self.selected_column) {
+            let (x, y) = (invader.get_x() + 2, invader.get_y() + invader.get_height());
+            if x < NUM_COLS && y < NUM_ROWS {
+                frame[x][y] = if self.shot_timer.ready { '*' } else { '.' };
+            }
+        }
+    }
 
-        let mut downwards = false;
+    pub fn selected_column(&self) -> usize {
+        self.selected_column
+    }
 
-        if self.direction == -1 {
-            let min_x = self.invaders.iter().map(|i| i.get_x()).min().unwrap();
-            if min_x == 0 {
-                self.direction = 1;
-                downwards = true;
+    /// How many milliseconds the commander has shifted each march step by,
+    /// negative is faster
+    pub fn march_nudge(&self) -> i64 {
+        self.march_nudge
+    }
+
+    /// Apply a commander's order, returns true if it fired a shot
+    pub fn command(&mut self, command: Command) -> bool {
+        match command {
+            Command::NextColumn => self.cycle_column(1),
+            Command::PrevColumn => self.cycle_column(-1),
+            Command::Faster => self.nudge(-NUDGE_STEP_MS),
+            Command::Slower => self.nudge(NUDGE_STEP_MS),
+            Command::Fire => return self.fire_selected(),
+        }
+        false
+    }
+
+    fn cycle_column(&mut self, direction: isize) {
+        let n = NUM_FORMATION_COLS as isize;
+        let mut column = self.selected_column as isize;
+        // skip over columns that have been wiped out
+        for _ in 0..NUM_FORMATION_COLS {
+            column = (column + direction).rem_euclid(n);
+            if self.column_shooter(column as usize).is_some() {
+                self.selected_column = column as usize;
+                return;
             }
-        } else {
-            let max_x = self
-                .invaders
-                .iter()
-                .map(|i| i.get_x() + i.get_width())
-                .max()
-                .unwrap();
-            if max_x == NUM_COLS - 1 {
-                self.direction = -1;
-                downwards = true;
+        }
+    }
+
+    fn nudge(&mut self, ms: i64) {
+        let limit = self.nudge_limit();
+        self.march_nudge = (self.march_nudge + ms).clamp(-limit, limit);
+    }
+
+    // the commander may shift the march by at most half the current step
+    fn nudge_limit(&self) -> i64 {
+        self.move_timer.duration.as_millis() as i64 / 2
+    }
+
+    fn reset_move_timer(&mut self) {
+        self.move_timer.reset();
+        let limit = self.nudge_limit();
+        let nudge = self.march_nudge.clamp(-limit, limit);
+        let millis = self.move_timer.duration.as_millis() as i64 + nudge;
+        self.move_timer.time_left = Duration::from_millis(millis as u64);
+    }
+
+    /// The bottom-most living invader in a column, the only one with a clear line of fire
+    pub fn column_shooter(&self, column: usize) -> Option<&Invader> {
+        self.invaders
+            .iter()
+            .filter(|i| i.is_alive && i.column() == column)
+            .max_by_key(|i| i.get_y())
+    }
+
+    fn fire_selected(&mut self) -> bool {
+        if !self.shot_timer.ready {
+            return false;
+        }
+
+        if self.column_shooter(self.selected_column).is_none() {
+            self.cycle_column(1);
+        }
+
+        let shot = self
+            .column_shooter(self.selected_column)
+            .map(|invader| Self::shot_from(invader, self.shot_sprite.clone()));
+
+        match shot {
+            Some(shot) => {
+                self.shots.push(shot);
+                self.shot_timer = Timer::from_millis(COMMAND_RELOAD_MS);
+                true
             }
+            None => false,
         }
+    }
+
+    fn shot_from(invader: &Invader, sprite: Arc<Sprite>) -> Shot {
+        Shot::new(
+            invader.get_x() + 2,
+            invader.get_y() + 2,
+            crate::shot::Direction::Down,
+            sprite,
+        )
+    }
 
+    pub fn move_army(&mut self) {
+        if self.invaders.is_empty() {
+            return;
+        }
+
+        let downwards = self.at_edge();
         if downwards {
-            let new_duration = max(100, self.move_timer.duration.as_millis() - 100);
+            self.direction = -self.direction;
+            // faster every time it comes down, down to the tuning's minimum
+            let min = self.tuning.march_min_ms.max(0) as u128;
+            let new_duration = self
+                .move_timer
+                .duration
+                .as_millis()
+                .saturating_sub(100)
+                .max(min);
             self.move_timer = Timer::from_millis(new_duration as u64);
         }
 
@@ -78,22 +267,41 @@ impl Army {
         }
     }
 
-    pub fn check_collision(&mut self, other: &dyn Transform) -> Option<usize> {
+    // whether the next step goes down instead of sideways
+    fn at_edge(&self) -> bool {
+        if self.direction == -1 {
+            self.invaders.iter().map(|i| i.get_x()).min() == Some(0)
+        } else {
+            // wide sprites can start out past the edge already
+            self.invaders
+                .iter()
+                .map(|i| i.get_x() + i.get_width())
+                .max()
+                .is_some_and(|right| right >= NUM_COLS - 1)
+        }
+    }
+
+    /// Kills the first living invader hit by `other` and returns it
+    pub fn check_collision<T: Pixels>(
+        &mut self,
+        other: &T,
+        collision: Collision,
+    ) -> Option<&Invader> {
         for invader in &mut self.invaders {
-            if invader.is_alive && invader.check_collision(other) {
+            if invader.is_alive && collision.hits(invader, other) {
                 invader.kill();
-                return Some(invader.score);
+                return Some(invader);
             }
         }
         None
     }
 
-    pub fn get_shooter(&self) -> Option<&Invader> {
+    pub fn get_shooter(&mut self) -> Option<&Invader> {
         if self.invaders.is_empty() {
             return None;
         }
 
-        let index = rand::thread_rng().gen_range(0..self.invaders.len());
+        let index = self.rng.gen_range(0..self.invaders.len());
 
         let invader = &self.invaders[index];
 
@@ -108,6 +316,15 @@ impl Army {
         self.invaders.is_empty()
     }
 
+    /// How many rows are left between the lowest invader and the ground
+    pub fn rows_left(&self) -> usize {
+        self.invaders
+            .iter()
+            .map(|i| (NUM_ROWS - 1).saturating_sub(i.get_y() + i.get_height()))
+            .min()
+            .unwrap_or(NUM_ROWS)
+    }
+
     pub fn invaded(&self) -> bool {
         self.invaders
             .iter()
@@ -125,21 +342,36 @@ impl Updatable for Army {
             invader.update(delta);
         }
 
+        self.marched = self.move_timer.ready && !self.invaders.is_empty();
         if self.move_timer.ready {
             self.move_army();
-            self.move_timer.reset();
+            self.reset_move_timer();
+        }
+
+        // let renderers finer than a cell show the army on its way
+        let step = frame::progress(&self.move_timer);
+        let (dx, dy) = if self.at_edge() {
+            (0.0, step)
+        } else {
+            (self.direction as f32 * step, 0.0)
+        };
+        for invader in &mut self.invaders {
+            invader.set_offset(dx, dy);
         }
 
-        if self.shot_timer.ready {
-            if let Some(invader) = self.get_shooter() {
-                self.shots.push(Shot::new(
-                    invader.get_x() + 2,
-                    invader.get_y() + 2,
-                    crate::shot::Direction::Down,
-                ));
+        if self.shot_timer.ready && !self.commanded {
+            let sprite = self.shot_sprite.clone();
+            if let Some(shot) = self
+                .get_shooter()
+                .map(|invader| Self::shot_from(invader, sprite))
+            {
+                self.shots.push(shot);
             }
-            // reset timer with a random value between 500 and 8000
-            self.shot_timer = Timer::from_millis(rand::thread_rng().gen_range(500..8000));
+            // reset timer with a random value in the fire window
+            self.shot_timer = Timer::from_millis(
+                self.rng
+                    .gen_range(self.tuning.fire_min_ms..self.tuning.fire_max_ms),
+            );
             self.shot_timer.reset();
         }
 
@@ -159,5 +391,35 @@ impl Drawable for Army {
         for shot in &self.shots {
             shot.draw(frame);
         }
+
+        self.draw_aim(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marches_on_when_tuned_faster_than_a_step_comes_off() {
+        let tuning = Tuning {
+            march_ms: 50,
+            march_level_ms: 0,
+            march_min_ms: 50,
+            ..Tuning::default()
+        };
+        let mut army = Army::with_tuning(1, 1, tuning, &SpriteSheet::builtin());
+
+        let mut steps = 0;
+        for _ in 0..2000 {
+            army.update(Duration::from_millis(10));
+            if army.marched() {
+                steps += 1;
+            }
+            assert_eq!(army.move_timer.duration, Duration::from_millis(50));
+        }
+        // about every 50ms for 20s, turns at the edges included
+        assert!(steps >= 300, "only {} steps", steps);
+        assert!(army.invaders.iter().any(|invader| invader.get_y() > 2));
     }
 }
@@ -0,0 +1,89 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::sound::Sound;
+
+/// The default sound effects, compiled into the binary. They were generated
+/// with `--export-sfx` from the effects in `synth` and can be swapped for
+/// hand-made ones by replacing the files in `assets/sfx`.
+pub const SFX: [(&str, &[u8]); 8] = [
+    ("boom", include_bytes!("../assets/sfx/boom.wav")),
+    ("march1", include_bytes!("../assets/sfx/march1.wav")),
+    ("march2", include_bytes!("../assets/sfx/march2.wav")),
+    ("march3", include_bytes!("../assets/sfx/march3.wav")),
+    ("march4", include_bytes!("../assets/sfx/march4.wav")),
+    ("pew", include_bytes!("../assets/sfx/pew.wav")),
+    ("startup", include_bytes!("../assets/sfx/startup.wav")),
+    ("ufo", include_bytes!("../assets/sfx/ufo.wav")),
+];
+
+// Only files with these extensions are picked up from an asset directory
+const AUDIO_EXTENSIONS: [&str; 4] = ["wav", "ogg", "flac", "mp3"];
+
+/// Where to look for sound files that override the defaults, lowest
+/// priority first. An explicitly configured directory is the only one used.
+pub fn sfx_dirs(custom: Option<&str>) -> Vec<PathBuf> {
+    asset_dirs("sfx", custom)
+}
+
+/// Where to look for music replacing the built-in songs, like `sfx_dirs`
+pub fn music_dirs(custom: Option<&str>) -> Vec<PathBuf> {
+    asset_dirs("music", custom)
+}
+
+/// Where to look for `.sprites` files reskinning the game, like `sfx_dirs`
+pub fn sprite_dirs(custom: Option<&str>) -> Vec<PathBuf> {
+    asset_dirs("sprites", custom)
+}
+
+/// Where to look for theme directories picked by name
+pub fn theme_dirs() -> Vec<PathBuf> {
+    asset_dirs("themes", None)
+}
+
+fn asset_dirs(name: &str, custom: Option<&str>) -> Vec<PathBuf> {
+    if let Some(dir) = custom {
+        return vec![PathBuf::from(dir)];
+    }
+
+    let mut dirs = Vec::new();
+    if let Some(exe_dir) = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        dirs.push(exe_dir.join(name));
+    }
+    dirs.push(PathBuf::from(name));
+    dirs
+}
+
+/// Loads every audio file in `dir` into `sound`, named after the file.
+/// Files that can't be decoded are skipped with a warning, leaving the
+/// sound they were meant to replace as it was.
+pub fn load_sfx_dir<P: AsRef<Path>>(
+    sound: &mut Sound,
+    dir: P,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_audio = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+
+        if is_audio {
+            if let Err(e) = sound.add(&path) {
+                eprintln!("Skipping sound file {}: {}", path.display(), e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The next word on a line of a text asset, parsed. `None` if the line ran
+/// out or it doesn't parse.
+pub fn parse_word<T: std::str::FromStr>(word: Option<&str>) -> Option<T> {
+    word?.parse().ok()
+}
@@ -0,0 +1,163 @@
+use crate::{army::Army, frame::Transform, player::Player, NUM_COLS, NUM_ROWS};
+
+/// How often the game asks a bot for its next action. Human players are
+/// limited by key repeat, bots get a comparable rate.
+pub const THINK_MS: u64 = 40;
+
+// Shots further up than this are not worth dodging yet
+const DANGER_ROWS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Idle,
+    Left,
+    Right,
+    Fire,
+}
+
+/// A computer player. It gets to look at the game state every `THINK_MS`
+/// and answers with one action, just like a single key press.
+pub trait Bot {
+    fn act(&mut self, player: &Player, army: &Army) -> Action;
+}
+
+/// Returns whether the action did something, so callers can play sounds
+pub fn apply(action: Action, player: &mut Player) -> bool {
+    match action {
+        Action::Idle => false,
+        Action::Left => {
+            let x = player.x;
+            player.move_left();
+            x != player.x
+        }
+        Action::Right => {
+            let x = player.x;
+            player.move_right();
+            x != player.x
+        }
+        Action::Fire => player.shoot(),
+    }
+}
+
+/// Dodges incoming shots and otherwise lines up under the nearest invader.
+#[derive(Default)]
+pub struct Autopilot;
+
+impl Autopilot {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn is_safe(x: usize, player: &Player, army: &Army) -> bool {
+        !army.shots.iter().any(|shot| {
+            shot.get_y() + DANGER_ROWS >= player.y
+                && shot.get_x() + 1 >= x
+                && shot.get_x() <= x + player.get_width()
+        })
+    }
+
+    fn step_towards(from: usize, to: usize) -> Action {
+        match to.cmp(&from) {
+            std::cmp::Ordering::Less => Action::Left,
+            std::cmp::Ordering::Greater => Action::Right,
+            std::cmp::Ordering::Equal => Action::Idle,
+        }
+    }
+}
+
+impl Bot for Autopilot {
+    fn act(&mut self, player: &Player, army: &Army) -> Action {
+        let max_x = NUM_COLS - player.get_width();
+
+        if !Self::is_safe(player.x, player, army) {
+            // run to the closest spot without a shot coming down on it
+            for offset in 1..NUM_COLS {
+                if offset <= player.x && Self::is_safe(player.x - offset, player, army) {
+                    return Action::Left;
+                }
+                if player.x + offset <= max_x && Self::is_safe(player.x + offset, player, army) {
+                    return Action::Right;
+                }
+            }
+        }
+
+        // shots leave the cannon from its middle
+        let muzzle = player.x + player.get_width() / 2;
+
+        let lined_up = army.invaders.iter().any(|invader| {
+            invader.is_alive
+                && muzzle >= invader.get_x()
+                && muzzle < invader.get_x() + invader.get_width()
+        });
+        if lined_up && player.shots_left() > 0 {
+            return Action::Fire;
+        }
+
+        let target = army
+            .invaders
+            .iter()
+            .filter(|invader| invader.is_alive)
+            .min_by_key(|invader| {
+                let centre = invader.get_x() + invader.get_width() / 2;
+                // prefer the ones closest to landing
+                centre.abs_diff(muzzle) + (NUM_ROWS - invader.get_y())
+            });
+
+        let action = match target {
+            Some(invader) => {
+                let centre = invader.get_x() + invader.get_width() / 2;
+                let goal = centre.saturating_sub(player.get_width() / 2).min(max_x);
+                Self::step_towards(player.x, goal)
+            }
+            None => Action::Idle,
+        };
+
+        // don't walk into a shot while chasing a target
+        let next = match action {
+            Action::Left => player.x.saturating_sub(1),
+            Action::Right => player.x + 1,
+            _ => player.x,
+        };
+        if Self::is_safe(next, player, army) {
+            action
+        } else {
+            Action::Idle
+        }
+    }
+}
+
+/// A scripted player that walks from wall to wall firing whenever it can.
+/// Useful as a dumb baseline next to the `Autopilot`.
+pub struct Sweeper {
+    direction: Action,
+}
+
+impl Sweeper {
+    pub fn new() -> Self {
+        Self {
+            direction: Action::Right,
+        }
+    }
+}
+
+impl Default for Sweeper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bot for Sweeper {
+    fn act(&mut self, player: &Player, _army: &Army) -> Action {
+        if player.shots_left() > 0 {
+            return Action::Fire;
+        }
+
+        let max_x = NUM_COLS - player.get_width() - 1;
+        if player.x == 0 {
+            self.direction = Action::Right;
+        } else if player.x >= max_x {
+            self.direction = Action::Left;
+        }
+        self.direction
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    frame::{Drawable, Frame},
+    game::Game,
+    layers::{Layer, Layers},
+    pixels::{paint, Canvas},
+    render::RenderContext,
+    NUM_COLS, NUM_ROWS,
+};
+
+/// Every cell is a braille character of 2 by 4 dots
+pub const DOTS_X: usize = 2;
+pub const DOTS_Y: usize = 4;
+
+// Which bit of a braille character each dot is, by row and column
+const DOT_BITS: [[u32; DOTS_X]; DOTS_Y] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// Turns a canvas of `NUM_COLS * 2` by `NUM_ROWS * 4` dots into braille
+/// characters. A cell can only have one colour, the first dot found decides.
+pub fn to_frame(canvas: &Canvas, frame: &mut Frame) {
+    for x in 0..NUM_COLS {
+        for y in 0..NUM_ROWS {
+            let mut bits = 0;
+            let mut color = None;
+            for (dy, row) in DOT_BITS.iter().enumerate() {
+                for (dx, bit) in row.iter().enumerate() {
+                    if let Some(dot) = canvas.get(x * DOTS_X + dx, y * DOTS_Y + dy) {
+                        bits |= bit;
+                        color = color.or(Some(dot));
+                    }
+                }
+            }
+
+            let c = if bits == 0 {
+                ' '
+            } else {
+                char::from_u32(0x2800 + bits).unwrap_or(' ')
+            };
+            frame.set(x, y, c, color);
+            frame.set_background(x, y, None);
+        }
+    }
+}
+
+/// Draws the game in braille dots, with the HUD on top as text
+pub fn draw(game: &Game, layers: &mut Layers, context: &RenderContext) {
+    let mut canvas = Canvas::with_size(NUM_COLS * DOTS_X, NUM_ROWS * DOTS_Y);
+    paint(game, context, |thing, color| {
+        canvas.draw_dots(thing, 0, color)
+    });
+
+    to_frame(&canvas, layers.get_mut(Layer::Entities));
+    game.army.draw_aim(layers.get_mut(Layer::Entities));
+    game.status.draw(layers.get_mut(Layer::Hud));
+}
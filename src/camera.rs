@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    events::{Event, Subscriber},
+    frame::Updatable,
+};
+
+/// How far the playfield shakes at full strength, in cells
+pub const MAX_SHAKE: (f32, f32) = (2.0, 1.0);
+
+// How long the playfield shakes when the cannon is hit
+const PLAYER_HIT_SHAKE_MS: u64 = 400;
+
+// The playfield jumps to a new spot this often while shaking
+const JOLT_MS: u64 = 30;
+
+/// Game feel: shaking the playfield and freezing the game for a moment
+/// when the cannon is hit. Both only change what is shown and when, the
+/// game itself plays out the same.
+pub struct Camera {
+    /// 1 is a full shake, 0 none at all
+    shake_strength: f32,
+    hit_stop: Duration,
+    shake_left: Duration,
+    shake_length: Duration,
+    stop_left: Duration,
+    jolt_left: Duration,
+    offset: (i32, i32),
+    rng: StdRng,
+}
+
+impl Camera {
+    /// `shake` in percent of `MAX_SHAKE`, zero for either turns it off
+    pub fn new(seed: u64, shake: u8, hit_stop: Duration) -> Self {
+        Self {
+            shake_strength: shake.min(100) as f32 / 100.0,
+            hit_stop,
+            shake_left: Duration::ZERO,
+            shake_length: Duration::ZERO,
+            stop_left: Duration::ZERO,
+            jolt_left: Duration::ZERO,
+            offset: (0, 0),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn shake(&mut self, length: Duration) {
+        if self.shake_strength > 0.0 && length >= self.shake_left {
+            self.shake_left = length;
+            self.shake_length = length;
+            self.jolt_left = Duration::ZERO;
+        }
+    }
+
+    /// Freezes the game for the configured hit-stop
+    pub fn stop(&mut self) {
+        self.stop_left = self.stop_left.max(self.hit_stop);
+    }
+
+    /// Where the playfield is drawn, relative to where it belongs
+    pub fn offset(&self) -> (i32, i32) {
+        self.offset
+    }
+
+    /// How much game time passes in `delta` of real time, none during a hit-stop
+    pub fn game_time(&self, delta: Duration) -> Duration {
+        if self.stop_left.is_zero() {
+            delta
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+impl Updatable for Camera {
+    fn update(&mut self, delta: Duration) {
+        self.stop_left = self.stop_left.saturating_sub(delta);
+
+        self.shake_left = self.shake_left.saturating_sub(delta);
+        if self.shake_left.is_zero() {
+            self.offset = (0, 0);
+            return;
+        }
+
+        self.jolt_left = self.jolt_left.saturating_sub(delta);
+        if self.jolt_left.is_zero() {
+            // dies down as it goes on
+            let fade = self.shake_left.as_secs_f32() / self.shake_length.as_secs_f32();
+            let strength = self.shake_strength * fade;
+            let (x, y) = (MAX_SHAKE.0 * strength, MAX_SHAKE.1 * strength);
+            self.offset = (
+                self.rng.gen_range(-x..=x).round() as i32,
+                self.rng.gen_range(-y..=y).round() as i32,
+            );
+            self.jolt_left = Duration::from_millis(JOLT_MS);
+        }
+    }
+}
+
+impl Subscriber for Camera {
+    fn notify(&mut self, event: &Event) {
+        if let Event::PlayerHit { .. } = event {
+            self.shake(Duration::from_millis(PLAYER_HIT_SHAKE_MS));
+            self.stop();
+        }
+    }
+}
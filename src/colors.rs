@@ -0,0 +1,314 @@
+use std::{env, fs, path::PathBuf};
+
+use crossterm::style::Color;
+
+// max_colors is the fourteenth number in a terminfo entry
+const MAX_COLORS: usize = 13;
+
+// The sixteen colours every terminal has, in ANSI order
+const BASIC: [Color; 16] = [
+    Color::Black,
+    Color::DarkRed,
+    Color::DarkGreen,
+    Color::DarkYellow,
+    Color::DarkBlue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::Grey,
+    Color::DarkGrey,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+
+/// How many colours the terminal can show
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// The basic sixteen, whatever the user's palette makes of them
+    Ansi16,
+    /// xterm's 256, a 6x6x6 cube and a ramp of greys on top of the basic ones
+    Ansi256,
+    /// Any `#rrggbb`
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Goes by `COLORTERM`, then `TERM`, then the terminal's terminfo entry
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        if colorterm == "truecolor" || colorterm == "24bit" || cfg!(windows) {
+            return ColorDepth::TrueColor;
+        }
+
+        let raw_term = env::var("TERM").unwrap_or_default();
+        let term = raw_term.to_lowercase();
+        if term.ends_with("-direct") || term.contains("truecolor") {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            match max_colors(&raw_term) {
+                Some(colors) if colors >= 1 << 24 => ColorDepth::TrueColor,
+                Some(colors) if colors >= 256 => ColorDepth::Ansi256,
+                _ => ColorDepth::Ansi16,
+            }
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "truecolor" | "24bit" => Some(ColorDepth::TrueColor),
+            "256" => Some(ColorDepth::Ansi256),
+            "16" => Some(ColorDepth::Ansi16),
+            _ => None,
+        }
+    }
+
+    /// The colour the terminal can show that looks closest. The basic
+    /// sixteen are left alone, every terminal has them.
+    pub fn convert(&self, color: Color) -> Color {
+        match (self, color) {
+            (ColorDepth::Ansi256, Color::Rgb { r, g, b }) => {
+                Color::AnsiValue(nearest([r, g, b], 16..=255))
+            }
+            (ColorDepth::Ansi16, Color::Rgb { .. } | Color::AnsiValue(16..)) => {
+                BASIC[nearest(rgb(color), 0..=15) as usize]
+            }
+            _ => color,
+        }
+    }
+}
+
+/// What a colour looks like in xterm's default palette
+pub fn rgb(color: Color) -> [u8; 3] {
+    match color {
+        Color::Reset | Color::Black => [0, 0, 0],
+        Color::DarkRed => [205, 0, 0],
+        Color::DarkGreen => [0, 205, 0],
+        Color::DarkYellow => [205, 205, 0],
+        Color::DarkBlue => [0, 0, 238],
+        Color::DarkMagenta => [205, 0, 205],
+        Color::DarkCyan => [0, 205, 205],
+        Color::Grey => [229, 229, 229],
+        Color::DarkGrey => [127, 127, 127],
+        Color::Red => [255, 0, 0],
+        Color::Green => [0, 255, 0],
+        Color::Yellow => [255, 255, 0],
+        Color::Blue => [92, 92, 255],
+        Color::Magenta => [255, 0, 255],
+        Color::Cyan => [0, 255, 255],
+        Color::White => [255, 255, 255],
+        Color::Rgb { r, g, b } => [r, g, b],
+        Color::AnsiValue(value) => ansi_rgb(value),
+    }
+}
+
+fn ansi_rgb(value: u8) -> [u8; 3] {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    match value {
+        0..=15 => rgb(BASIC[value as usize]),
+        16..=231 => {
+            let cube = value - 16;
+            [
+                LEVELS[(cube / 36) as usize],
+                LEVELS[(cube / 6 % 6) as usize],
+                LEVELS[(cube % 6) as usize],
+            ]
+        }
+        _ => {
+            let grey = 8 + (value - 232) * 10;
+            [grey, grey, grey]
+        }
+    }
+}
+
+// the palette entry closest in CIELAB, where distance is roughly how
+// different colours look rather than how different their numbers are
+fn nearest(color: [u8; 3], candidates: impl Iterator<Item = u8>) -> u8 {
+    let target = lab(color);
+    let distance = |value: &u8| {
+        let [l, a, b] = lab(ansi_rgb(*value));
+        (l - target[0]).powi(2) + (a - target[1]).powi(2) + (b - target[2]).powi(2)
+    };
+    candidates
+        .min_by(|x, y| distance(x).total_cmp(&distance(y)))
+        .unwrap_or(0)
+}
+
+fn lab([r, g, b]: [u8; 3]) -> [f32; 3] {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(r), linear(g), linear(b));
+
+    // XYZ relative to the D65 white point
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+// the places ncurses looks for compiled terminfo entries
+fn terminfo_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    if let Ok(list) = env::var("TERMINFO_DIRS") {
+        dirs.extend(
+            list.split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        );
+    }
+    for dir in [
+        "/etc/terminfo",
+        "/lib/terminfo",
+        "/usr/share/terminfo",
+        "/usr/lib/terminfo",
+    ] {
+        dirs.push(PathBuf::from(dir));
+    }
+    dirs
+}
+
+fn max_colors(term: &str) -> Option<u32> {
+    let first = term.chars().next()?;
+    // entries are filed under their first letter, or its hex code on macOS
+    let subdirs = [first.to_string(), format!("{:x}", first as u32)];
+    terminfo_dirs()
+        .iter()
+        .flat_map(|dir| subdirs.iter().map(move |sub| dir.join(sub).join(term)))
+        .find_map(|path| fs::read(path).ok())
+        .and_then(|entry| parse_max_colors(&entry))
+}
+
+/// Reads `max_colors` out of a compiled terminfo entry, in the legacy
+/// format or ncurses' one with 32-bit numbers
+fn parse_max_colors(entry: &[u8]) -> Option<u32> {
+    let short = |at: usize| Some(u16::from_le_bytes([*entry.get(at)?, *entry.get(at + 1)?]));
+    let wide = match short(0)? {
+        0o432 => false,
+        0o1036 => true,
+        _ => return None,
+    };
+    let (names, bools, numbers) = (short(2)? as usize, short(4)? as usize, short(6)? as usize);
+    if numbers <= MAX_COLORS {
+        return None;
+    }
+
+    // the numbers start on an even byte after the names and booleans
+    let start = (12 + names + bools + 1) & !1;
+    let value = if wide {
+        let at = start + MAX_COLORS * 4;
+        i32::from_le_bytes(entry.get(at..at + 4)?.try_into().ok()?)
+    } else {
+        short(start + MAX_COLORS * 2)? as i16 as i32
+    };
+    // a missing capability is -1
+    u32::try_from(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a compiled terminfo entry with max_colors set and every other number
+    // missing. The odd-sized names and booleans need a byte of padding.
+    fn entry(wide: bool, colors: i32) -> Vec<u8> {
+        let mut entry = Vec::new();
+        let magic: u16 = if wide { 0o1036 } else { 0o432 };
+        for value in [magic, 2, 1, MAX_COLORS as u16 + 1, 0, 0] {
+            entry.extend(value.to_le_bytes());
+        }
+        entry.extend(b"x\0");
+        entry.push(0);
+        entry.push(0);
+        for i in 0..=MAX_COLORS {
+            let value = if i == MAX_COLORS { colors } else { -1 };
+            if wide {
+                entry.extend(value.to_le_bytes());
+            } else {
+                entry.extend((value as i16).to_le_bytes());
+            }
+        }
+        entry
+    }
+
+    #[test]
+    fn reads_max_colors_from_terminfo() {
+        assert_eq!(parse_max_colors(&entry(false, 256)), Some(256));
+        assert_eq!(parse_max_colors(&entry(true, 1 << 24)), Some(1 << 24));
+        assert_eq!(parse_max_colors(&entry(false, -1)), None);
+        assert_eq!(parse_max_colors(b"not terminfo"), None);
+    }
+
+    // the only test that touches these variables, so it can't race another.
+    // Windows terminals are always taken to have true colour.
+    #[test]
+    #[cfg(not(windows))]
+    fn detects_from_the_environment() {
+        let dir = env::temp_dir().join(format!("invaders-terminfo-{}", std::process::id()));
+        fs::create_dir_all(dir.join("i")).unwrap();
+        fs::write(dir.join("i").join("invaders-test"), entry(false, 256)).unwrap();
+        env::set_var("TERMINFO", &dir);
+
+        let detect = |colorterm: &str, term: &str| {
+            env::set_var("COLORTERM", colorterm);
+            env::set_var("TERM", term);
+            ColorDepth::detect()
+        };
+        assert_eq!(detect("truecolor", "xterm"), ColorDepth::TrueColor);
+        assert_eq!(detect("", "xterm-direct"), ColorDepth::TrueColor);
+        assert_eq!(detect("", "xterm-256color"), ColorDepth::Ansi256);
+        assert_eq!(detect("", "xterm"), ColorDepth::Ansi16);
+        assert_eq!(detect("", "invaders-test"), ColorDepth::Ansi256);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn converts_to_the_nearest_palette_colour() {
+        let red = Color::Rgb { r: 255, g: 0, b: 0 };
+        assert_eq!(ColorDepth::Ansi256.convert(red), Color::AnsiValue(196));
+        assert_eq!(ColorDepth::Ansi16.convert(red), Color::Red);
+        assert_eq!(ColorDepth::TrueColor.convert(red), red);
+
+        let grey = Color::Rgb {
+            r: 128,
+            g: 128,
+            b: 128,
+        };
+        assert_eq!(ColorDepth::Ansi256.convert(grey), Color::AnsiValue(244));
+        assert_eq!(ColorDepth::Ansi16.convert(grey), Color::DarkGrey);
+
+        // the basic sixteen are left alone, the rest of the 256 aren't
+        assert_eq!(ColorDepth::Ansi16.convert(Color::Blue), Color::Blue);
+        assert_eq!(
+            ColorDepth::Ansi16.convert(Color::AnsiValue(46)),
+            Color::Green
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    army::Tuning,
+    bot::{self, Action},
+    events::Event,
+    frame::{new_frame, Drawable, Frame, Transform},
+    game::{Game, GameOver},
+    sprites::SpriteSheet,
+};
+
+/// Reward given for every life lost, on top of the points scored
+pub const LIFE_LOST_REWARD: f64 = -100.0;
+
+/// Game time simulated by a single `Env::step`
+pub const STEP_MS: u64 = bot::THINK_MS;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntityKind {
+    Player,
+    Invader,
+    PlayerShot,
+    InvaderShot,
+}
+
+#[derive(Clone, Debug)]
+pub struct Entity {
+    pub kind: EntityKind,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Entity {
+    fn new(kind: EntityKind, transform: &dyn Transform) -> Self {
+        Self {
+            kind,
+            x: transform.get_x(),
+            y: transform.get_y(),
+            width: transform.get_width(),
+            height: transform.get_height(),
+        }
+    }
+}
+
+/// The game as an agent sees it: both the character grid a human would see
+/// and a list of everything on screen.
+pub struct Observation {
+    pub frame: Frame,
+    pub entities: Vec<Entity>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Info {
+    pub score: usize,
+    pub lives: usize,
+    pub level: u64,
+    pub steps: u64,
+    pub game_over: Option<GameOver>,
+}
+
+/// A gym style environment for training agents headlessly. Time only moves
+/// forward in `step`, so it runs as fast as the CPU allows.
+pub struct Env {
+    game: Game,
+    steps: u64,
+    sprites: Arc<SpriteSheet>,
+}
+
+impl Env {
+    pub fn new() -> Self {
+        Self::with_sprites(Arc::new(SpriteSheet::builtin()))
+    }
+
+    /// Games are played with these sprites and their hitboxes
+    pub fn with_sprites(sprites: Arc<SpriteSheet>) -> Self {
+        Self {
+            game: Game::with_sprites(0, Tuning::default(), sprites.clone()),
+            steps: 0,
+            sprites,
+        }
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.game = Game::with_sprites(seed, Tuning::default(), self.sprites.clone());
+        self.steps = 0;
+        self.observe()
+    }
+
+    pub fn step(&mut self, action: Action) -> (Observation, f64, bool, Info) {
+        let mut reward = 0.0;
+
+        if self.game.game_over().is_none() {
+            self.game.act(action);
+            self.game.update(Duration::from_millis(STEP_MS));
+
+            for event in self.game.events.publish(&mut []) {
+                match event {
+                    Event::InvaderKilled { score, .. } => reward += score as f64,
+                    Event::PlayerHit { .. } => reward += LIFE_LOST_REWARD,
+                    _ => {}
+                }
+            }
+
+            self.steps += 1;
+        }
+
+        let info = Info {
+            score: self.game.status.score,
+            lives: self.game.player.lives,
+            level: self.game.status.level,
+            steps: self.steps,
+            game_over: self.game.game_over(),
+        };
+
+        (self.observe(), reward, info.game_over.is_some(), info)
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    fn observe(&self) -> Observation {
+        let mut frame = new_frame();
+        self.game.draw(&mut frame);
+
+        let player = &self.game.player;
+        let army = &self.game.army;
+
+        let mut entities = Vec::new();
+        if !player.dead() {
+            entities.push(Entity::new(EntityKind::Player, player));
+        }
+        for invader in army.invaders.iter().filter(|i| i.is_alive) {
+            entities.push(Entity::new(EntityKind::Invader, invader));
+        }
+        for shot in &player.shots {
+            entities.push(Entity::new(EntityKind::PlayerShot, shot));
+        }
+        for shot in &army.shots {
+            entities.push(Entity::new(EntityKind::InvaderShot, shot));
+        }
+
+        Observation { frame, entities }
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
+}
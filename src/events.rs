@@ -0,0 +1,66 @@
+use crate::game::GameOver;
+
+/// Something that happened in the game that other parts may want to react to
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The player fired, from where the shot starts
+    ShotFired {
+        x: usize,
+        y: usize,
+    },
+    /// An invader of the given kind was shot down at the shot's position
+    InvaderKilled {
+        kind: usize,
+        x: usize,
+        y: usize,
+        score: usize,
+    },
+    /// The cannon was hit, `x`, `y` is its centre
+    PlayerHit {
+        x: usize,
+        y: usize,
+        lives: usize,
+    },
+    PlayerRespawned,
+    /// The army took a step
+    ArmyMarched,
+    /// The level that was just cleared, the next one starts right away
+    LevelCleared {
+        level: u64,
+    },
+    GameOver(GameOver),
+}
+
+/// Anything that wants to hear about game events: sound, statistics,
+/// achievements, visual effects...
+pub trait Subscriber {
+    fn notify(&mut self, event: &Event);
+}
+
+/// Collects the events of a game update until they are published.
+#[derive(Default)]
+pub struct EventBus {
+    events: Vec<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn emit(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    /// Hands every collected event to every subscriber, in order, and
+    /// returns them for the caller to look at as well
+    pub fn publish(&mut self, subscribers: &mut [&mut dyn Subscriber]) -> Vec<Event> {
+        let events: Vec<Event> = self.events.drain(..).collect();
+        for event in &events {
+            for subscriber in subscribers.iter_mut() {
+                subscriber.notify(event);
+            }
+        }
+        events
+    }
+}
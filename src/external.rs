@@ -0,0 +1,308 @@
+use std::{
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    army::Army,
+    bot::{Action, Bot},
+    env::Env,
+    frame::Transform,
+    player::Player,
+    shot::Shot,
+    sprites::SpriteSheet,
+};
+
+/// How long an external bot may think about a tick before it counts as idle
+pub const DEFAULT_TIMEOUT_MS: u64 = 100;
+
+/// Games in a tournament are cut off after this many steps
+pub const MAX_STEPS: u64 = 50_000;
+
+/// A bot running as a separate program. Every tick it receives the game
+/// state as one line of JSON on stdin and answers with one line on stdout:
+///
+/// ```text
+/// > {"tick":1,"player":{"x":36,"y":21,"width":7,"lives":3,"shots_left":3,"alive":true},"invaders":[{"x":9,"y":2,"width":5,"height":2,"score":40}],"player_shots":[],"invader_shots":[{"x":11,"y":6}]}
+/// < {"tick":1,"action":"fire"}
+/// ```
+///
+/// Valid actions are `idle`, `left`, `right` and `fire`. An answer has to
+/// echo the tick it is for, answers that don't arrive within the timeout
+/// are thrown away and the bot idles. The bot is only sent a new state
+/// once it has answered the last one or run out of time.
+pub struct ExternalBot {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    lines: Receiver<String>,
+    timeout: Duration,
+    tick: u64,
+    /// The tick it is thinking about and when it has to answer by
+    waiting: Option<(u64, Instant)>,
+    blocking: bool,
+    pub timeouts: u64,
+}
+
+impl ExternalBot {
+    /// Starts `command`, which is split on whitespace into program and arguments
+    pub fn spawn(command: &str, timeout: Duration) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or("Empty bot command")?;
+
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take().ok_or("Bot has no stdout")?;
+
+        // read on a separate thread so a silent bot can't stall the game
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+            timeout,
+            tick: 0,
+            waiting: None,
+            blocking: false,
+            timeouts: 0,
+        })
+    }
+
+    /// Waits for the answer to every state instead of picking up whatever
+    /// has arrived so far. Only for games that don't run in real time, like
+    /// tournaments, as the game stops while the bot thinks.
+    pub fn blocking(mut self) -> Self {
+        self.blocking = true;
+        self
+    }
+
+    // the next line the bot has written, if there is one by `deadline`
+    fn next_line(&mut self, deadline: Instant) -> Option<String> {
+        let line = if self.blocking {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            self.lines
+                .recv_timeout(wait)
+                .map_err(|e| e == RecvTimeoutError::Disconnected)
+        } else {
+            self.lines
+                .try_recv()
+                .map_err(|e| e == TryRecvError::Disconnected)
+        };
+
+        match line {
+            Ok(line) => Some(line),
+            Err(disconnected) => {
+                if disconnected {
+                    // the bot has gone away, stop talking to it
+                    self.stdin = None;
+                }
+                None
+            }
+        }
+    }
+}
+
+impl Bot for ExternalBot {
+    fn act(&mut self, player: &Player, army: &Army) -> Action {
+        let (tick, deadline) = match self.waiting {
+            Some(waiting) => waiting,
+            None => {
+                let Some(stdin) = &mut self.stdin else {
+                    return Action::Idle;
+                };
+                self.tick += 1;
+                let state = state_json(self.tick, player, army);
+                if writeln!(stdin, "{}", state)
+                    .and_then(|_| stdin.flush())
+                    .is_err()
+                {
+                    self.stdin = None;
+                    return Action::Idle;
+                }
+                let waiting = (self.tick, Instant::now() + self.timeout);
+                self.waiting = Some(waiting);
+                waiting
+            }
+        };
+
+        while let Some(line) = self.next_line(deadline) {
+            // answers for a tick that already timed out are thrown away
+            if parse_tick(&line) == Some(tick) {
+                self.waiting = None;
+                return parse_action(&line).unwrap_or(Action::Idle);
+            }
+        }
+
+        if self.stdin.is_none() {
+            self.waiting = None;
+        } else if Instant::now() >= deadline {
+            self.timeouts += 1;
+            self.waiting = None;
+        }
+        Action::Idle
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        self.stdin = None;
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn state_json(tick: u64, player: &Player, army: &Army) -> String {
+    let mut json = String::new();
+    let _ = write!(
+        json,
+        "{{\"tick\":{},\"player\":{{\"x\":{},\"y\":{},\"width\":{},\"lives\":{},\"shots_left\":{},\"alive\":{}}}",
+        tick,
+        player.x,
+        player.y,
+        player.get_width(),
+        player.lives,
+        player.shots_left(),
+        !player.dead()
+    );
+
+    json.push_str(",\"invaders\":[");
+    for (i, invader) in army.invaders.iter().filter(|i| i.is_alive).enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        let _ = write!(
+            json,
+            "{{\"x\":{},\"y\":{},\"width\":{},\"height\":{},\"score\":{}}}",
+            invader.get_x(),
+            invader.get_y(),
+            invader.get_width(),
+            invader.get_height(),
+            invader.score
+        );
+    }
+
+    json.push(']');
+
+    write_shots(&mut json, "player_shots", &player.shots);
+    write_shots(&mut json, "invader_shots", &army.shots);
+
+    json.push('}');
+    json
+}
+
+fn write_shots(json: &mut String, name: &str, shots: &[Shot]) {
+    let _ = write!(json, ",\"{}\":[", name);
+    for (i, shot) in shots.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        let _ = write!(json, "{{\"x\":{},\"y\":{}}}", shot.get_x(), shot.get_y());
+    }
+    json.push(']');
+}
+
+// what comes after `"name":` in a bot's answer
+fn field<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let key = format!("\"{}\"", name);
+    let rest = &line[line.find(&key)? + key.len()..];
+    Some(rest.trim_start().strip_prefix(':')?.trim_start())
+}
+
+/// Picks the value of `"tick"` out of a bot's answer
+fn parse_tick(line: &str) -> Option<u64> {
+    let rest = field(line, "tick")?;
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+/// Picks the value of `"action"` out of a bot's answer
+fn parse_action(line: &str) -> Option<Action> {
+    let rest = field(line, "action")?.strip_prefix('"')?;
+    let value = &rest[..rest.find('"')?];
+
+    match value {
+        "idle" => Some(Action::Idle),
+        "left" => Some(Action::Left),
+        "right" => Some(Action::Right),
+        "fire" => Some(Action::Fire),
+        _ => None,
+    }
+}
+
+/// How one bot did over all the seeds of a tournament
+pub struct Standing {
+    pub command: String,
+    pub scores: Vec<usize>,
+    pub timeouts: u64,
+}
+
+impl Standing {
+    pub fn total(&self) -> usize {
+        self.scores.iter().sum()
+    }
+
+    pub fn best(&self) -> usize {
+        self.scores.iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// Plays every bot through the same seeds, best total score first
+pub fn tournament(
+    commands: &[String],
+    seeds: &[u64],
+    timeout: Duration,
+    sprites: Arc<SpriteSheet>,
+) -> Result<Vec<Standing>, Box<dyn std::error::Error>> {
+    let mut env = Env::with_sprites(sprites);
+    let mut standings = Vec::new();
+
+    for command in commands {
+        let mut standing = Standing {
+            command: command.clone(),
+            scores: Vec::new(),
+            timeouts: 0,
+        };
+
+        for &seed in seeds {
+            // a fresh process per game so bots can't carry state across seeds
+            let mut bot = ExternalBot::spawn(command, timeout)?.blocking();
+            env.reset(seed);
+
+            loop {
+                let action = bot.act(&env.game().player, &env.game().army);
+                let (_, _, done, info) = env.step(action);
+                if done || info.steps >= MAX_STEPS {
+                    standing.scores.push(info.score);
+                    break;
+                }
+            }
+
+            standing.timeouts += bot.timeouts;
+        }
+
+        standings.push(standing);
+    }
+
+    standings.sort_by_key(|standing| std::cmp::Reverse(standing.total()));
+    Ok(standings)
+}
@@ -1,11 +1,65 @@
-use std::time::Duration;
+use std::{
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+
+use crossterm::style::Color;
+
+use rusty_time::timer::Timer;
 
 use crate::{NUM_COLS, NUM_ROWS};
 
-pub type Frame = Vec<Vec<char>>;
+/// Characters indexed as `frame[x][y]`, plus the colours of each cell.
+/// Cells written through indexing keep whatever colours they had, which for
+/// a fresh frame are the theme's defaults.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    cells: Vec<Vec<char>>,
+    colors: Vec<Vec<Option<Color>>>,
+    backgrounds: Vec<Vec<Option<Color>>>,
+}
+
+impl Frame {
+    pub fn color(&self, x: usize, y: usize) -> Option<Color> {
+        self.colors[x][y]
+    }
+
+    pub fn background(&self, x: usize, y: usize) -> Option<Color> {
+        self.backgrounds[x][y]
+    }
+
+    /// `None` is the theme's background
+    pub fn set_background(&mut self, x: usize, y: usize, color: Option<Color>) {
+        self.backgrounds[x][y] = color;
+    }
+
+    /// Writes a character in a colour, `None` is the default colour
+    pub fn set(&mut self, x: usize, y: usize, c: char, color: Option<Color>) {
+        self.cells[x][y] = c;
+        self.colors[x][y] = color;
+    }
+}
+
+impl Deref for Frame {
+    type Target = Vec<Vec<char>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.cells
+    }
+}
+
+impl DerefMut for Frame {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.cells
+    }
+}
 
 pub fn new_frame() -> Frame {
-    vec![vec![' '; NUM_ROWS]; NUM_COLS]
+    Frame {
+        cells: vec![vec![' '; NUM_ROWS]; NUM_COLS],
+        colors: vec![vec![None; NUM_ROWS]; NUM_COLS],
+        backgrounds: vec![vec![None; NUM_ROWS]; NUM_COLS],
+    }
 }
 
 pub trait Drawable {
@@ -21,18 +75,35 @@ pub trait Transform {
     fn get_y(&self) -> usize;
     fn get_width(&self) -> usize;
     fn get_height(&self) -> usize;
+    /// Where it is drawn, in cells. Things on their way to the next cell
+    /// are partly there, for renderers that can show less than a cell.
+    fn position(&self) -> (f32, f32) {
+        (self.get_x() as f32, self.get_y() as f32)
+    }
+    /// The part that can be hit as x, y, width and height, the whole
+    /// thing unless a sprite says otherwise
+    fn hitbox(&self) -> (usize, usize, usize, usize) {
+        (
+            self.get_x(),
+            self.get_y(),
+            self.get_width(),
+            self.get_height(),
+        )
+    }
     fn check_collision(&self, other: &dyn Transform) -> bool {
-        let x = self.get_x();
-        let y = self.get_y();
-        let width = self.get_width();
-        let height = self.get_height();
-        let other_x = other.get_x();
-        let other_y = other.get_y();
-        let other_width = other.get_width();
-        let other_height = other.get_height();
+        let (x, y, width, height) = self.hitbox();
+        let (other_x, other_y, other_width, other_height) = other.hitbox();
         x < other_x + other_width
             && x + width > other_x
             && y < other_y + other_height
             && y + height > other_y
     }
 }
+
+/// How far a timer has run, from 0.0 when it starts to 1.0 when it's ready
+pub fn progress(timer: &Timer) -> f32 {
+    if timer.ready || timer.duration.is_zero() {
+        return 1.0;
+    }
+    (1.0 - timer.time_left.as_secs_f32() / timer.duration.as_secs_f32()).clamp(0.0, 1.0)
+}
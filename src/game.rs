@@ -0,0 +1,266 @@
+use std::{sync::Arc, time::Duration};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rusty_time::timer::Timer;
+
+use crate::{
+    army::{Army, Tuning},
+    bot::{self, Action},
+    events::{Event, EventBus},
+    frame::{Drawable, Frame, Transform, Updatable},
+    layers::{Layer, Layers},
+    pixels::Collision,
+    player::Player,
+    sprites::SpriteSheet,
+    status::Status,
+    NUM_COLS,
+};
+
+// The cannon stays gone at least this long after being hit
+const MIN_RESPAWN_MS: u64 = 1000;
+
+/// Why a game ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameOver {
+    /// The last life was lost to an invader shot
+    Shot,
+    /// The invaders reached the bottom of the screen
+    Invaded,
+}
+
+/// The whole simulation without any terminal or audio attached, so it can
+/// be driven by the interactive loop in `main.rs` as well as headlessly.
+///
+/// Everything that happens is emitted on `events`, whoever drives the game
+/// is expected to publish them after every update.
+pub struct Game {
+    pub status: Status,
+    pub player: Player,
+    pub army: Army,
+    pub events: EventBus,
+    death_timer: Timer,
+    game_over: Option<GameOver>,
+    collision: Collision,
+    sprites: Arc<SpriteSheet>,
+    rng: StdRng,
+}
+
+impl Game {
+    pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+
+    /// A game that plays out the same way every time for the same seed and input
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_tuning(seed, Tuning::default())
+    }
+
+    pub fn with_tuning(seed: u64, tuning: Tuning) -> Self {
+        Self::with_sprites(seed, tuning, Arc::new(SpriteSheet::builtin()))
+    }
+
+    /// Sprites decide how big things are, so they're part of the game and
+    /// not only how it looks
+    pub fn with_sprites(seed: u64, tuning: Tuning, sprites: Arc<SpriteSheet>) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let status = Status::new();
+        let army = Army::with_tuning(status.level, rng.gen(), tuning, &sprites);
+        let player = Player::new(&sprites);
+
+        Self {
+            status,
+            player,
+            army,
+            events: EventBus::new(),
+            death_timer: Timer::from_millis(MIN_RESPAWN_MS),
+            game_over: None,
+            collision: Collision::default(),
+            sprites,
+            rng,
+        }
+    }
+
+    pub fn game_over(&self) -> Option<GameOver> {
+        self.game_over
+    }
+
+    /// See `Renderer::collision`, hits should match what's on screen
+    pub fn set_collision(&mut self, collision: Collision) {
+        self.collision = collision;
+    }
+
+    /// Moves or fires the cannon, does nothing while it is dead
+    pub fn act(&mut self, action: Action) {
+        if self.player.dead() || self.game_over.is_some() {
+            return;
+        }
+
+        if bot::apply(action, &mut self.player) && action == Action::Fire {
+            if let Some(shot) = self.player.shots.last() {
+                self.events.emit(Event::ShotFired {
+                    x: shot.get_x(),
+                    y: shot.get_y(),
+                });
+            }
+        }
+    }
+
+    pub fn update(&mut self, delta: Duration) {
+        if self.game_over.is_some() {
+            // the last hit still blows the cannon up
+            if self.player.dead() {
+                self.player.update(delta);
+            }
+            return;
+        }
+
+        if !self.player.dead() {
+            self.player.update(delta);
+            self.army.update(delta);
+            if self.army.marched() {
+                self.events.emit(Event::ArmyMarched);
+            }
+
+            // Collision detection
+            for shot in &mut self.player.shots {
+                if let Some(invader) = self.army.check_collision(shot, self.collision) {
+                    self.status.add_score(invader.score);
+                    shot.explode();
+                    self.events.emit(Event::InvaderKilled {
+                        kind: invader.kind(),
+                        x: shot.get_x(),
+                        y: shot.get_y(),
+                        score: invader.score,
+                    });
+                }
+            }
+
+            // invader shots go straight through while the cannon can't be hit
+            let invulnerable = self.player.invulnerable();
+            for shot in &mut self.army.shots {
+                if !invulnerable && self.collision.hits(&self.player, shot) {
+                    shot.explode();
+                    if let Some(versus) = &mut self.status.versus {
+                        versus.hits += 1;
+                    }
+
+                    let lives = self.player.die();
+                    self.events.emit(Event::PlayerHit {
+                        x: self.player.get_x() + self.player.get_width() / 2,
+                        y: self.player.get_y() + self.player.get_height() / 2,
+                        lives: lives.unwrap_or(0),
+                    });
+                    match lives {
+                        None => {
+                            self.end(GameOver::Shot);
+                            return;
+                        }
+                        Some(lives) => {
+                            // back once the explosion has played out
+                            let respawn_ms = self.player.explosion_ms().max(MIN_RESPAWN_MS);
+                            self.death_timer = Timer::from_millis(respawn_ms);
+                            self.status.update_lives(lives);
+                        }
+                    }
+                }
+            }
+        } else {
+            self.player.update(delta);
+            self.death_timer.update(delta);
+            if self.death_timer.ready {
+                self.player.x = self.respawn_x();
+                self.player.resurrect();
+                self.events.emit(Event::PlayerRespawned);
+            }
+        }
+
+        if self.army.invaded() {
+            self.end(GameOver::Invaded);
+            return;
+        }
+
+        if self.army.all_dead() {
+            self.events.emit(Event::LevelCleared {
+                level: self.status.level,
+            });
+            self.next_level();
+        }
+
+        if let Some(versus) = &mut self.status.versus {
+            versus.column = self.army.selected_column();
+            versus.march_nudge = self.army.march_nudge();
+        }
+    }
+
+    // where the cannon died, unless an invader shot is coming down there,
+    // then the nearest place that's clear
+    fn respawn_x(&self) -> usize {
+        let width = self.player.get_width();
+        let max_x = NUM_COLS - width;
+        let start = self.player.x.min(max_x);
+        // a column to either side as well, to give the player a moment
+        let clear = |x: usize| {
+            !self
+                .army
+                .shots
+                .iter()
+                .any(|shot| shot.get_x() + 1 >= x && shot.get_x() <= x + width)
+        };
+
+        for distance in 0..=max_x {
+            let candidates = [start.checked_sub(distance), Some(start + distance)];
+            for x in candidates.into_iter().flatten() {
+                if x <= max_x && clear(x) {
+                    return x;
+                }
+            }
+        }
+        start
+    }
+
+    fn end(&mut self, reason: GameOver) {
+        self.game_over = Some(reason);
+        self.events.emit(Event::GameOver(reason));
+    }
+
+    fn next_level(&mut self) {
+        self.status.level_up();
+        let commanded = self.army.commanded();
+        self.army = Army::with_tuning(
+            self.status.level,
+            self.rng.gen(),
+            self.army.tuning(),
+            &self.sprites,
+        );
+        self.army.set_commanded(commanded);
+        self.player.reset_lives();
+        self.status.update_lives(self.player.lives);
+        self.player.shots.clear();
+        self.army.shots.clear();
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game {
+    /// Draws everything on the layer it belongs to
+    pub fn draw_layers(&self, layers: &mut Layers) {
+        let entities: Vec<&dyn Drawable> = vec![&self.player, &self.army];
+        for drawable in entities {
+            drawable.draw(layers.get_mut(Layer::Entities));
+        }
+        self.status.draw(layers.get_mut(Layer::Hud));
+    }
+}
+
+impl Drawable for Game {
+    fn draw(&self, frame: &mut Frame) {
+        let mut layers = Layers::new();
+        self.draw_layers(&mut layers);
+        layers.flatten(frame);
+    }
+}
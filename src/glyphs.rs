@@ -0,0 +1,55 @@
+use std::env;
+
+/// The characters the terminal is sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GlyphSet {
+    /// The original look, needs a terminal with a Unicode font
+    Unicode,
+    /// Plain 7-bit characters for VT100-class terminals and serial consoles
+    Ascii,
+}
+
+impl GlyphSet {
+    /// Unicode if the locale says the terminal speaks UTF-8, ASCII otherwise
+    pub fn detect() -> Self {
+        if cfg!(windows) {
+            return GlyphSet::Unicode;
+        }
+
+        // the first of these that is set decides, like setlocale does
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        if locale.contains("utf-8") || locale.contains("utf8") {
+            GlyphSet::Unicode
+        } else {
+            GlyphSet::Ascii
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "unicode" => Some(GlyphSet::Unicode),
+            "ascii" => Some(GlyphSet::Ascii),
+            _ => None,
+        }
+    }
+
+    /// The character to show for `c`. Sprites are written with Unicode
+    /// characters, the ASCII set has stand-ins for them.
+    pub fn convert(&self, c: char) -> char {
+        if *self == GlyphSet::Unicode || c.is_ascii() {
+            return c;
+        }
+        match c {
+            '☺' => 'o',
+            '↑' => '|',
+            '↓' => '!',
+            _ => '*',
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use std::{
+    env,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{cursor, QueueableCommand};
+
+use crate::{
+    colors::rgb,
+    frame::{new_frame, Frame},
+    game::Game,
+    pixels::{paint, Canvas},
+    render::RenderContext,
+    NUM_COLS, NUM_ROWS,
+};
+
+/// The image covers the playfield. The HUD on the top row stays text and the
+/// bottom row is left free, so a sixel image never scrolls the screen.
+pub const FIRST_ROW: usize = 1;
+pub const IMAGE_ROWS: usize = NUM_ROWS - 2;
+
+/// Canvas pixels per cell, every bitmap pixel is 2x2 of them
+pub const PIXELS_X: usize = 2;
+pub const PIXELS_Y: usize = 4;
+
+// Size of a cell in screen pixels when the terminal won't tell
+const DEFAULT_CELL: (usize, usize) = (10, 20);
+
+// Images are a lot bigger than text updates, so they're sent at most this often
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+// kitty takes the image in chunks of at most this many base64 bytes
+const KITTY_CHUNK: usize = 4096;
+
+// The one image kitty shows, replaced every frame
+const KITTY_IMAGE_ID: u32 = 1;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// How images get to the terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// kitty's graphics protocol, also spoken by WezTerm and Ghostty
+    Kitty,
+    /// DEC sixel, as drawn by foot, mlterm and WezTerm
+    Sixel,
+}
+
+impl Protocol {
+    /// Guesses from the environment, as asking the terminal means reading its
+    /// answer off stdin. Nothing inside tmux or screen, they'd eat the images.
+    pub fn detect() -> Option<Self> {
+        let var = |name| env::var(name).unwrap_or_default().to_lowercase();
+        let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+
+        if term.starts_with("screen") || term.starts_with("tmux") || env::var_os("TMUX").is_some() {
+            None
+        } else if env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+            || program == "wezterm"
+            || program == "ghostty"
+        {
+            Some(Protocol::Kitty)
+        } else if term.starts_with("foot") || term.contains("mlterm") || term.contains("sixel") {
+            Some(Protocol::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+/// Sends the playfield to the terminal as an image
+pub struct Graphics {
+    protocol: Protocol,
+    /// Size of a cell in screen pixels
+    cell: (usize, usize),
+    last: Option<Canvas>,
+    sent: Option<Instant>,
+}
+
+impl Graphics {
+    pub fn new(protocol: Protocol) -> Self {
+        Self::with_cell_size(protocol, cell_size().unwrap_or(DEFAULT_CELL))
+    }
+
+    pub fn with_cell_size(protocol: Protocol, cell: (usize, usize)) -> Self {
+        Self {
+            protocol,
+            cell,
+            last: None,
+            sent: None,
+        }
+    }
+
+    /// Writes the image, unless it hasn't changed or the last one went out
+    /// very recently
+    pub fn render<W: Write>(
+        &mut self,
+        out: &mut W,
+        canvas: &Canvas,
+        context: &RenderContext,
+    ) -> io::Result<()> {
+        let recent = self
+            .sent
+            .is_some_and(|sent| sent.elapsed() < FRAME_INTERVAL);
+        if recent || self.last.as_ref() == Some(canvas) {
+            return Ok(());
+        }
+
+        self.write(out, canvas, context)?;
+        self.last = Some(canvas.clone());
+        self.sent = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Writes the image over the playfield, whatever was sent before
+    pub fn write<W: Write>(
+        &self,
+        out: &mut W,
+        canvas: &Canvas,
+        context: &RenderContext,
+    ) -> io::Result<()> {
+        let background = rgb(context.theme.background);
+        out.queue(cursor::MoveTo(0, FIRST_ROW as u16))?;
+        match self.protocol {
+            Protocol::Kitty => kitty(out, canvas, background)?,
+            Protocol::Sixel => {
+                // stretched so the image covers the cells it's meant to
+                let scale = (
+                    (self.cell.0 / PIXELS_X).max(1),
+                    (self.cell.1 / PIXELS_Y).max(1),
+                );
+                sixel(out, canvas, background, scale)?
+            }
+        }
+        out.flush()
+    }
+
+    /// Takes the image off the screen. Sixels are just cells, the text
+    /// renderer or leaving the alternate screen clears them.
+    pub fn clear<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if self.protocol == Protocol::Kitty {
+            write!(out, "\x1b_Ga=d,d=I,i={},q=2\x1b\\", KITTY_IMAGE_ID)?;
+            out.flush()?;
+        }
+        self.last = None;
+        Ok(())
+    }
+}
+
+/// Writes the canvas as a kitty image that the terminal scales to the
+/// playfield. Empty pixels are the background colour.
+pub fn kitty<W: Write>(out: &mut W, canvas: &Canvas, background: [u8; 3]) -> io::Result<()> {
+    let mut data = Vec::with_capacity(canvas.width() * canvas.height() * 3);
+    for y in 0..canvas.height() {
+        for x in 0..canvas.width() {
+            data.extend_from_slice(&canvas.get(x, y).map_or(background, rgb));
+        }
+    }
+    let payload = base64(&data);
+    let chunks: Vec<&[u8]> = payload.chunks(KITTY_CHUNK).collect();
+
+    // the old image goes first, or kitty would stack them up
+    write!(out, "\x1b_Ga=d,d=I,i={},q=2\x1b\\", KITTY_IMAGE_ID)?;
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        if i == 0 {
+            // below the text so the HUD stays readable, and the cursor stays put
+            write!(
+                out,
+                "\x1b_Ga=T,f=24,s={},v={},c={},r={},i={},C=1,z=-1,q=2,m={};",
+                canvas.width(),
+                canvas.height(),
+                NUM_COLS,
+                IMAGE_ROWS,
+                KITTY_IMAGE_ID,
+                more
+            )?;
+        } else {
+            write!(out, "\x1b_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        out.write_all(b"\x1b\\")?;
+    }
+    Ok(())
+}
+
+/// Writes the canvas as a sixel image, every canvas pixel `scale` screen
+/// pixels wide and high. Empty pixels are the background colour.
+pub fn sixel<W: Write>(
+    out: &mut W,
+    canvas: &Canvas,
+    background: [u8; 3],
+    scale: (usize, usize),
+) -> io::Result<()> {
+    let (scale_x, scale_y) = (scale.0.max(1), scale.1.max(1));
+    let (width, height) = (canvas.width() * scale_x, canvas.height() * scale_y);
+
+    // colour registers, the background is always the first one
+    let mut palette = vec![background];
+    let mut registers = Vec::with_capacity(canvas.width() * canvas.height());
+    for y in 0..canvas.height() {
+        for x in 0..canvas.width() {
+            let color = canvas.get(x, y).map_or(background, rgb);
+            let register = match palette.iter().position(|c| *c == color) {
+                Some(register) => register,
+                None => {
+                    palette.push(color);
+                    palette.len() - 1
+                }
+            };
+            registers.push(register);
+        }
+    }
+
+    write!(out, "\x1bP0;1;0q\"1;1;{};{}", width, height)?;
+    for (register, [r, g, b]) in palette.iter().enumerate() {
+        write!(
+            out,
+            "#{};2;{};{};{}",
+            register,
+            percent(*r),
+            percent(*g),
+            percent(*b)
+        )?;
+    }
+
+    // six rows of screen pixels at a time, one pass per colour
+    for band in (0..height).step_by(6) {
+        for register in 0..palette.len() {
+            let mut row = Vec::with_capacity(width);
+            for x in 0..canvas.width() {
+                let mut bits = 0;
+                for dy in 0..6 {
+                    let y = (band + dy) / scale_y;
+                    if y < canvas.height() && registers[y * canvas.width() + x] == register {
+                        bits |= 1 << dy;
+                    }
+                }
+                row.extend(std::iter::repeat_n(b'?' + bits, scale_x));
+            }
+
+            while row.last() == Some(&b'?') {
+                row.pop();
+            }
+            if row.is_empty() {
+                continue;
+            }
+            write!(out, "#{}", register)?;
+            run_length(out, &row)?;
+            out.write_all(b"$")?;
+        }
+        out.write_all(b"-")?;
+    }
+    out.write_all(b"\x1b\\")
+}
+
+// sixel's repeat introducer, `!<count><sixel>`, for runs where it's shorter
+fn run_length<W: Write>(out: &mut W, row: &[u8]) -> io::Result<()> {
+    let mut i = 0;
+    while i < row.len() {
+        let run = row[i..].iter().take_while(|c| **c == row[i]).count();
+        if run > 3 {
+            write!(out, "!{}", run)?;
+            out.write_all(&row[i..i + 1])?;
+        } else {
+            out.write_all(&row[i..i + run])?;
+        }
+        i += run;
+    }
+    Ok(())
+}
+
+// sixel colours are in percent
+fn percent(value: u8) -> u32 {
+    (value as u32 * 100 + 127) / 255
+}
+
+fn base64(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(bits >> (18 - i * 6)) & 0x3f]);
+            } else {
+                encoded.push(b'=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(unix)]
+fn cell_size() -> Option<(usize, usize)> {
+    // SAFETY: TIOCGWINSZ only fills in the winsize it is handed
+    let size = unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) != 0 {
+            return None;
+        }
+        size
+    };
+    if size.ws_col == 0 || size.ws_row == 0 || size.ws_xpixel == 0 || size.ws_ypixel == 0 {
+        return None;
+    }
+    Some((
+        size.ws_xpixel as usize / size.ws_col as usize,
+        size.ws_ypixel as usize / size.ws_row as usize,
+    ))
+}
+
+#[cfg(not(unix))]
+fn cell_size() -> Option<(usize, usize)> {
+    None
+}
+
+/// The playfield as an image, `PIXELS_X` by `PIXELS_Y` pixels per cell. The
+/// image pixels are the same dots braille has, hits go by them as well.
+pub fn canvas(game: &Game, context: &RenderContext) -> Canvas {
+    let mut canvas = Canvas::with_size(NUM_COLS * PIXELS_X, IMAGE_ROWS * PIXELS_Y);
+    let top = FIRST_ROW * PIXELS_Y;
+    paint(game, context, |thing, color| {
+        canvas.draw_dots(thing, top, color)
+    });
+    canvas
+}
+
+/// The rows of a text frame that the image leaves uncovered. The rest is
+/// dropped, effects drawn there like stars and particles don't show.
+pub fn hud(frame: &Frame) -> Frame {
+    let mut hud = new_frame();
+    let covered = FIRST_ROW..FIRST_ROW + IMAGE_ROWS;
+    for x in 0..NUM_COLS {
+        for y in (0..NUM_ROWS).filter(|y| !covered.contains(y)) {
+            hud.set(x, y, frame[x][y], frame.color(x, y));
+            hud.set_background(x, y, frame.background(x, y));
+        }
+    }
+    hud
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Color;
+
+    use super::*;
+
+    const BLACK: [u8; 3] = [0, 0, 0];
+
+    // two pixels side by side, the left one red
+    fn red_dot() -> Canvas {
+        let mut canvas = Canvas::with_size(2, 1);
+        canvas.plot(0, 0, Color::Red);
+        canvas
+    }
+
+    fn text(out: &[u8]) -> String {
+        String::from_utf8(out.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn base64_pads_to_whole_groups() {
+        assert_eq!(text(&base64(b"")), "");
+        assert_eq!(text(&base64(b"f")), "Zg==");
+        assert_eq!(text(&base64(b"fo")), "Zm8=");
+        assert_eq!(text(&base64(b"foo")), "Zm9v");
+        assert_eq!(text(&base64(b"foobar")), "Zm9vYmFy");
+        assert_eq!(text(&base64(&[0xff, 0xef])), "/+8=");
+    }
+
+    #[test]
+    fn run_length_only_shortens_long_runs() {
+        let encode = |row: &[u8]| {
+            let mut out = Vec::new();
+            run_length(&mut out, row).unwrap();
+            text(&out)
+        };
+        assert_eq!(encode(b"???"), "???");
+        assert_eq!(encode(b"????"), "!4?");
+        assert_eq!(encode(b"@@@@@@AB~~~~~"), "!6@AB!5~");
+    }
+
+    #[test]
+    fn kitty_sends_one_chunk_for_a_small_image() {
+        let mut out = Vec::new();
+        kitty(&mut out, &red_dot(), BLACK).unwrap();
+        assert_eq!(
+            text(&out),
+            format!(
+                "\x1b_Ga=d,d=I,i=1,q=2\x1b\\\
+                 \x1b_Ga=T,f=24,s=2,v=1,c={},r={},i=1,C=1,z=-1,q=2,m=0;/wAAAAAA\x1b\\",
+                NUM_COLS, IMAGE_ROWS
+            )
+        );
+    }
+
+    #[test]
+    fn kitty_splits_big_images_into_chunks() {
+        // 1200 pixels are 3600 bytes, 4800 in base64
+        let mut out = Vec::new();
+        kitty(&mut out, &Canvas::with_size(40, 30), BLACK).unwrap();
+        let out = text(&out);
+        let commands: Vec<&str> = out.split_terminator("\x1b\\").collect();
+
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[0], "\x1b_Ga=d,d=I,i=1,q=2");
+        let (header, first) = commands[1].split_once(';').unwrap();
+        assert!(header.starts_with("\x1b_Ga=T,f=24,s=40,v=30,"));
+        assert!(header.ends_with(",m=1"));
+        assert_eq!(first.len(), KITTY_CHUNK);
+        let (header, second) = commands[2].split_once(';').unwrap();
+        assert_eq!(header, "\x1b_Gm=0");
+        assert_eq!(second.len(), 4800 - KITTY_CHUNK);
+        assert!(second.bytes().all(|c| c == b'A'));
+    }
+
+    #[test]
+    fn sixel_paints_each_colour_in_its_own_pass() {
+        let mut out = Vec::new();
+        sixel(&mut out, &red_dot(), BLACK, (1, 1)).unwrap();
+        // the red pass leaves out the empty pixel at the end
+        assert_eq!(
+            text(&out),
+            "\x1bP0;1;0q\"1;1;2;1#0;2;0;0;0#1;2;100;0;0#0?@$#1@$-\x1b\\"
+        );
+    }
+
+    #[test]
+    fn sixel_scales_pixels_up() {
+        let mut out = Vec::new();
+        sixel(&mut out, &red_dot(), BLACK, (2, 3)).unwrap();
+        // every pixel is two sixels wide and the top three bits of one high
+        assert_eq!(
+            text(&out),
+            "\x1bP0;1;0q\"1;1;4;3#0;2;0;0;0#1;2;100;0;0#0??FF$#1FF$-\x1b\\"
+        );
+    }
+
+    #[test]
+    fn sixel_shortens_runs() {
+        let mut out = Vec::new();
+        sixel(&mut out, &Canvas::with_size(5, 1), BLACK, (1, 1)).unwrap();
+        assert_eq!(text(&out), "\x1bP0;1;0q\"1;1;5;1#0;2;0;0;0#0!5@$-\x1b\\");
+    }
+}
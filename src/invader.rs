@@ -1,23 +1,23 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use rusty_time::timer::Timer;
 
-use crate::frame::{Drawable, Frame, Transform, Updatable};
-
-// There are 5 different invaders, each have 2 animation frames
-const INVADER_ANIMS: [[&str; 2]; 5] = [
-    [" /^\\ \n|^|^|", " /^\\ \n ||| "],
-    ["|_☺_ \n | ||", " _☺_|\n|| |"],
-    ["~T~T~\n /~\\ ", "/P^P\\\n |~| "],
-    ["/~~~\\\n\\-V-/", "/~~~\\\n /V\\ "],
-    ["/___\\\n / \\ ", "\\___/\n \\V/ "],
-];
+use crate::{
+    frame::{Drawable, Frame, Transform, Updatable},
+    sprites::{Sprite, SpriteSheet},
+};
 
 pub struct Invader {
     pub is_alive: bool,
+    kind: usize,
+    column: usize,
     x: usize,
     y: usize,
-    anim: Vec<&'static str>,
+    // how far along the next march step is, in cells
+    offset: (f32, f32),
+    // one of the 5 invader sprites, `invader0` to `invader4`
+    sprite: Arc<Sprite>,
+    explosion: Arc<Sprite>,
     anim_idx: usize,
     anim_timer: Timer,
     remove: bool,
@@ -25,15 +25,16 @@ pub struct Invader {
 }
 
 impl Invader {
-    pub fn new(x: usize, y: usize) -> Self {
-        let anim = match y {
-            2 => INVADER_ANIMS[0],
-            5 => INVADER_ANIMS[1],
-            8 => INVADER_ANIMS[2],
-            11 => INVADER_ANIMS[3],
-            14 => INVADER_ANIMS[4],
+    pub fn new(x: usize, y: usize, sheet: &SpriteSheet) -> Self {
+        let kind = match y {
+            2 => 0,
+            5 => 1,
+            8 => 2,
+            11 => 3,
+            14 => 4,
             _ => panic!("Invalid invader starting position"),
         };
+        let sprite = sheet.get(&format!("invader{}", kind));
 
         let score = match y {
             2 => 40,
@@ -45,11 +46,15 @@ impl Invader {
         };
 
         Self {
+            kind,
+            column: (x - 9) / 7,
             x,
             y,
-            anim: anim.to_vec(),
+            offset: (0.0, 0.0),
+            anim_timer: Timer::from_millis(sprite.duration_ms(0)),
+            sprite,
+            explosion: sheet.get("explosion"),
             anim_idx: 0,
-            anim_timer: Timer::from_millis(500),
             is_alive: true,
             remove: false,
             score,
@@ -58,17 +63,38 @@ impl Invader {
 
     pub fn kill(&mut self) {
         self.is_alive = false;
-        self.anim_timer = Timer::from_millis(500);
+        self.anim_idx = 0;
+        self.anim_timer = Timer::from_millis(self.explosion.duration_ms(0));
     }
 
     pub fn dead(&self) -> bool {
         !self.is_alive
     }
 
+    /// Which of the invader types this is, 0 is the top row
+    pub fn kind(&self) -> usize {
+        self.kind
+    }
+
+    /// Which animation frame is showing
+    pub fn frame(&self) -> usize {
+        self.anim_idx
+    }
+
+    /// The formation column this invader started in, counted from the left
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
     pub fn removable(&self) -> bool {
         self.remove
     }
 
+    /// Set by the army as it gets ready for its next step
+    pub fn set_offset(&mut self, dx: f32, dy: f32) {
+        self.offset = (dx, dy);
+    }
+
     pub fn move_down(&mut self) {
         self.y += 1;
     }
@@ -89,11 +115,25 @@ impl Transform for Invader {
     }
 
     fn get_width(&self) -> usize {
-        5
+        self.sprite.width()
     }
 
     fn get_height(&self) -> usize {
-        2
+        self.sprite.height()
+    }
+
+    fn position(&self) -> (f32, f32) {
+        (self.x as f32 + self.offset.0, self.y as f32 + self.offset.1)
+    }
+
+    fn hitbox(&self) -> (usize, usize, usize, usize) {
+        let hitbox = self.sprite.hitbox();
+        (
+            self.x + hitbox.x,
+            self.y + hitbox.y,
+            hitbox.width,
+            hitbox.height,
+        )
     }
 }
 
@@ -101,33 +141,27 @@ impl Updatable for Invader {
     fn update(&mut self, delta: Duration) {
         self.anim_timer.update(delta);
         if self.anim_timer.ready {
-            if !self.is_alive {
+            let sprite = if self.is_alive {
+                &self.sprite
+            } else {
+                &self.explosion
+            };
+            if !self.is_alive && self.anim_idx + 1 >= sprite.len() {
                 self.remove = true;
             }
-            self.anim_idx = (self.anim_idx + 1) % self.anim.len();
-            self.anim_timer.reset();
+            self.anim_idx = (self.anim_idx + 1) % sprite.len();
+            self.anim_timer = Timer::from_millis(sprite.duration_ms(self.anim_idx));
         }
     }
 }
 
 impl Drawable for Invader {
     fn draw(&self, frame: &mut Frame) {
-        if !self.is_alive && !self.anim_timer.ready {
-            // Draw the explosion
-            frame[self.x + 1][self.y] = '\\';
-            frame[self.x + 3][self.y] = '/';
-            frame[self.x + 1][self.y + 1] = '/';
-            frame[self.x + 3][self.y + 1] = '\\';
-
+        if !self.is_alive {
+            self.explosion.draw(frame, self.x, self.y, self.anim_idx);
             return;
         }
 
-        let anim = self.anim[self.anim_idx];
-        let lines = anim.lines();
-        for (i, line) in lines.enumerate() {
-            for (j, c) in line.chars().enumerate() {
-                frame[self.x + j][self.y + i] = c;
-            }
-        }
+        self.sprite.draw(frame, self.x, self.y, self.anim_idx);
     }
 }
@@ -0,0 +1,101 @@
+use crate::{
+    frame::{new_frame, Frame},
+    NUM_COLS, NUM_ROWS,
+};
+
+/// Where something is drawn, from the back to the front
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    /// Scenery that never gets in the way
+    Background,
+    /// The cannon, the invaders and their shots
+    Entities,
+    /// Things that are only for show, like explosions
+    Effects,
+    /// Score and lives
+    Hud,
+    /// Messages on top of everything
+    Overlay,
+}
+
+impl Layer {
+    /// In the order they are stacked, back to front
+    pub const ALL: [Layer; 5] = [
+        Layer::Background,
+        Layer::Entities,
+        Layer::Effects,
+        Layer::Hud,
+        Layer::Overlay,
+    ];
+
+    /// Whether it moves when the playfield shakes, the HUD stays put
+    pub fn in_playfield(&self) -> bool {
+        *self < Layer::Hud
+    }
+}
+
+/// A frame for every layer, flattened into a single one for the renderer.
+/// A space without a background colour is transparent, so the padding
+/// around a sprite doesn't erase what's behind it. To blank out a cell,
+/// give it a background.
+pub struct Layers {
+    frames: Vec<Frame>,
+}
+
+impl Layers {
+    pub fn new() -> Self {
+        Self {
+            frames: Layer::ALL.iter().map(|_| new_frame()).collect(),
+        }
+    }
+
+    pub fn get(&self, layer: Layer) -> &Frame {
+        &self.frames[layer as usize]
+    }
+
+    pub fn get_mut(&mut self, layer: Layer) -> &mut Frame {
+        &mut self.frames[layer as usize]
+    }
+
+    /// Stacks the layers onto `frame`, later ones covering earlier ones
+    /// wherever they have something drawn
+    pub fn flatten(&self, frame: &mut Frame) {
+        self.flatten_shifted(frame, (0, 0));
+    }
+
+    /// Like `flatten`, with the playfield moved `offset` cells right and
+    /// down. What moves off the edge is cut off.
+    pub fn flatten_shifted(&self, frame: &mut Frame, offset: (i32, i32)) {
+        for (layer, contents) in Layer::ALL.iter().zip(&self.frames) {
+            let (dx, dy) = if layer.in_playfield() { offset } else { (0, 0) };
+            for x in 0..NUM_COLS {
+                for y in 0..NUM_ROWS {
+                    let (from_x, from_y) = (x as i32 - dx, y as i32 - dy);
+                    if from_x < 0 || from_y < 0 {
+                        continue;
+                    }
+                    let (from_x, from_y) = (from_x as usize, from_y as usize);
+                    if from_x >= NUM_COLS || from_y >= NUM_ROWS {
+                        continue;
+                    }
+
+                    let c = contents[from_x][from_y];
+                    let background = contents.background(from_x, from_y);
+                    if c != ' ' || background.is_some() {
+                        frame.set(x, y, c, contents.color(from_x, from_y));
+                    }
+                    // a character on its own keeps the background behind it
+                    if background.is_some() {
+                        frame.set_background(x, y, background);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
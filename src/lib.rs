@@ -1,11 +1,36 @@
+pub mod achievements;
 pub mod army;
+pub mod assets;
+pub mod bot;
+pub mod braille;
+pub mod camera;
+pub mod colors;
+pub mod env;
+pub mod events;
+pub mod external;
 pub mod frame;
+pub mod game;
+pub mod glyphs;
+pub mod graphics;
 pub mod invader;
+pub mod layers;
+pub mod music;
+pub mod options;
+pub mod particles;
+pub mod pixels;
 pub mod player;
 pub mod render;
+pub mod settings;
 pub mod shot;
+pub mod simulate;
 pub mod sound;
+pub mod spectate;
+pub mod sprites;
+pub mod starfield;
 pub mod status;
+pub mod synth;
+pub mod theme;
+pub mod title;
 
 pub const NUM_ROWS: usize = 24;
 pub const NUM_COLS: usize = 80;
@@ -1,4 +1,4 @@
-use std::{io, time::Duration};
+use std::{io, sync::Arc, time::Duration};
 
 use crossterm::{
     cursor::Hide,
@@ -6,23 +6,178 @@ use crossterm::{
     terminal, ExecutableCommand,
 };
 use invaders_2::{
-    army::Army,
-    frame::{new_frame, Drawable, Transform, Updatable},
-    player::Player,
-    render,
-    sound::Sound,
-    status::Status,
+    achievements::Achievements,
+    army::Command,
+    assets,
+    bot::{self, Action, Autopilot, Bot, Sweeper},
+    camera::Camera,
+    colors::ColorDepth,
+    events,
+    external::{self, ExternalBot},
+    frame::{new_frame, Drawable, Updatable},
+    game::Game,
+    glyphs::GlyphSet,
+    graphics::{self, Graphics},
+    layers::{Layer, Layers},
+    music::{Music, Scene},
+    options::Options,
+    particles::Particles,
+    render::{self, RenderContext, Renderer},
+    settings::Settings,
+    simulate,
+    sound::{Sound, SoundEffects},
+    spectate::{self, Broadcaster},
+    sprites::SpriteSheet,
+    starfield::Starfield,
+    synth,
+    theme::Theme,
+    title::Title,
 };
 use rusty_time::timer::Timer;
 use tokio::time::Instant;
 
+// How much the volume keys change the master volume
+const VOLUME_STEP: f32 = 0.1;
+
+// The music turns menacing once the invaders are this close to the ground
+const DANGER_ROWS: usize = 4;
+
+// Sounds still playing at the end are cut off after this long
+const EXIT_WAIT: Duration = Duration::from_secs(10);
+
+// How much faster the music plays with one invader left than with a full army
+const MAX_TEMPO_BOOST: f32 = 0.75;
+
+// Mutes or changes the master volume, false if `code` isn't a volume key
+fn volume_key(code: KeyCode, sound: &mut Sound) -> bool {
+    let mut mixer = *sound.mixer();
+    match code {
+        KeyCode::Char('m') => mixer.muted = !mixer.muted,
+        KeyCode::Char('-') => mixer.master = (mixer.master - VOLUME_STEP).max(0.0),
+        KeyCode::Char('+') | KeyCode::Char('=') => {
+            mixer.master = (mixer.master + VOLUME_STEP).min(1.0)
+        }
+        _ => return false,
+    }
+    sound.set_mixer(mixer);
+    true
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut audio = Sound::new();
+    let mut options = Options::from_args()?;
 
-    if let Ok(dir) = std::fs::read_dir("sfx") {
-        for entry in dir {
-            audio.add(entry.as_ref().unwrap().path())?;
+    let settings = Settings::load();
+    // a theme that has gone missing from the settings is not worth failing over
+    let theme = match &options.theme {
+        Some(name) => Theme::find(name)?,
+        None => Theme::find(&settings.theme).unwrap_or_default(),
+    };
+
+    // sprites affect hitboxes, so simulations load them as well
+    let mut sheet = SpriteSheet::builtin();
+    theme.apply(&mut sheet);
+    for dir in assets::sprite_dirs(options.sprites.as_deref()) {
+        if dir.is_dir() {
+            sheet.load_dir(&dir)?;
+        } else if options.sprites.is_some() {
+            return Err(format!("Sprite directory {} not found", dir.display()).into());
+        }
+    }
+    let theme_sfx = theme.sfx.clone();
+    let context = RenderContext {
+        glyphs: options.glyphs.unwrap_or_else(GlyphSet::detect),
+        sprites: Arc::new(sheet),
+        theme: Arc::new(theme),
+        colors: options.colors.unwrap_or_else(ColorDepth::detect),
+    };
+    if context.glyphs == GlyphSet::Ascii && options.renderer.needs_unicode() {
+        eprintln!("Half-blocks and braille need Unicode, drawing with text instead");
+        options.renderer = Renderer::Text;
+    }
+
+    if let Some(addr) = &options.watch {
+        return spectate::watch(addr).await;
+    }
+
+    if let Some(dir) = &options.export_sfx {
+        synth::export(dir)?;
+        println!("Sound effects written to {}", dir);
+        return Ok(());
+    }
+
+    let bot_timeout = Duration::from_millis(options.bot_timeout);
+
+    if options.simulate {
+        let games = options.seeds.unwrap_or(1000);
+        let (tuning, sprites) = (options.tuning, context.sprites.clone());
+        let invulnerability = Duration::from_millis(options.invulnerable_ms);
+        let report = match options.player.as_str() {
+            "autopilot" => simulate::simulate(games, tuning, invulnerability, sprites, || {
+                Box::new(Autopilot::new())
+            }),
+            "sweeper" => simulate::simulate(games, tuning, invulnerability, sprites, || {
+                Box::new(Sweeper::new())
+            }),
+            other => return Err(format!("Unknown player: {}", other).into()),
+        };
+        print!("{}", report);
+        return Ok(());
+    }
+
+    if options.tournament {
+        let seeds: Vec<u64> = (0..options.seeds.unwrap_or(10)).collect();
+        let standings =
+            external::tournament(&options.bots, &seeds, bot_timeout, context.sprites.clone())?;
+        println!("Rank  Total     Best      Timeouts  Bot");
+        for (rank, standing) in standings.iter().enumerate() {
+            println!(
+                "{:<5} {:<9} {:<9} {:<9} {}",
+                rank + 1,
+                standing.total(),
+                standing.best(),
+                standing.timeouts,
+                standing.command
+            );
+        }
+        return Ok(());
+    }
+
+    let broadcaster = match &options.broadcast {
+        Some(addr) => Some(Broadcaster::bind(addr, context.clone()).await?),
+        None => None,
+    };
+
+    let mut audio = if options.no_sound {
+        Sound::silent()
+    } else {
+        Sound::with_voices(settings.voices)
+    };
+    audio.set_mixer(settings.mixer());
+    audio.add_defaults()?;
+    if let Some(dir) = &theme_sfx {
+        assets::load_sfx_dir(&mut audio, dir)?;
+    }
+
+    // files in the asset directories replace the defaults of the same name
+    for dir in assets::sfx_dirs(options.sfx.as_deref()) {
+        if dir.is_dir() {
+            assets::load_sfx_dir(&mut audio, &dir)?;
+        } else if options.sfx.is_some() {
+            return Err(format!("Sound directory {} not found", dir.display()).into());
+        }
+    }
+
+    let mut music = if options.no_music {
+        Music::silent()
+    } else {
+        Music::new()
+    };
+    for dir in assets::music_dirs(options.music.as_deref()) {
+        if dir.is_dir() {
+            music.load_dir(&dir);
+        } else if options.music.is_some() {
+            return Err(format!("Music directory {} not found", dir.display()).into());
         }
     }
 
@@ -46,126 +201,263 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    let mut graphics = match options.renderer {
+        Renderer::Graphics(protocol) => Some(Graphics::new(protocol)),
+        _ => None,
+    };
+
     let (render_tx, mut render_rx) = tokio::sync::mpsc::channel(1);
 
+    let render_context = context.clone();
     let render_handle = tokio::spawn(async move {
         let mut last_frame = new_frame();
         let mut stdout = io::stdout();
-        render::render(&mut stdout, &last_frame, &last_frame, true);
+        render::render(&mut stdout, &last_frame, &last_frame, true, &render_context);
 
         loop {
-            let curr_frame = match render_rx.recv().await {
-                Some(frame) => frame,
+            let (curr_frame, image) = match render_rx.recv().await {
+                Some(message) => message,
                 None => break,
             };
 
-            render::render(&mut stdout, &last_frame, &curr_frame, false);
+            if let Some(broadcaster) = &broadcaster {
+                broadcaster.publish(&curr_frame);
+            }
+            // spectators get the whole text frame, here the image covers the playfield
+            let curr_frame = match &graphics {
+                Some(_) => graphics::hud(&curr_frame),
+                None => curr_frame,
+            };
+
+            render::render(
+                &mut stdout,
+                &last_frame,
+                &curr_frame,
+                false,
+                &render_context,
+            );
+            if let (Some(graphics), Some(image)) = (&mut graphics, &image) {
+                let _ = graphics.render(&mut stdout, image, &render_context);
+            }
             last_frame = curr_frame;
         }
+
+        if let Some(graphics) = &mut graphics {
+            let _ = graphics.clear(&mut stdout);
+        }
     });
 
     let mut instant = Instant::now();
 
-    let mut status = Status::new();
-    let mut player = Player::new();
-    let mut invaders = Army::new(status.level);
+    let seed = rand::random();
+    let mut game = Game::with_sprites(seed, options.tuning, context.sprites.clone());
+    game.player
+        .set_invulnerability(Duration::from_millis(options.invulnerable_ms));
+    let mut starfield = (settings.stars && !options.no_stars).then(|| Starfield::new(seed));
+    let mut particles = Particles::new(seed, context.clone());
+    particles.set_flash(settings.flash && !options.no_flash);
+    let hit_stop = Duration::from_millis(options.hit_stop.unwrap_or(settings.hit_stop_ms));
+    let mut camera = Camera::new(seed, options.shake.unwrap_or(settings.shake), hit_stop);
+    game.set_collision(options.renderer.collision());
 
-    let mut death_timer = Timer::from_millis(1000);
+    if options.versus {
+        game.status.enable_versus();
+        game.army.set_commanded(true);
+    }
 
-    audio.play("startup");
+    let mut autopilot: Option<Box<dyn Bot>> = if let Some(command) = options.bots.first() {
+        Some(Box::new(ExternalBot::spawn(command, bot_timeout)?))
+    } else if options.autoplay {
+        Some(Box::new(Autopilot::new()))
+    } else {
+        None
+    };
+    let mut bot_timer = Timer::from_millis(bot::THINK_MS);
 
-    'gameloop: loop {
-        let delta = instant.elapsed();
-        instant = Instant::now();
+    let mut sound_effects = SoundEffects::new(audio, music);
+    let mut achievements = Achievements::new();
+    let mut army_size = game.army.invaders.len();
+
+    sound_effects.sound.play("startup");
 
+    // the title tune plays until the player is ready, bots start right away
+    let mut started = autopilot.is_some();
+    if !started {
+        sound_effects.set_scene(Scene::Title);
+        let mut layers = Layers::new();
+        if let Some(starfield) = &starfield {
+            starfield.draw(layers.get_mut(Layer::Background));
+        }
+        options.renderer.draw(&game, &mut layers, &context);
+        Title.draw(layers.get_mut(Layer::Overlay));
         let mut frame = new_frame();
+        layers.flatten(&mut frame);
+        let _ = render_tx
+            .send((frame, options.renderer.image(&game, &context)))
+            .await;
 
-        if !player.dead() {
-            // Input
+        'title: loop {
             while event::poll(Duration::default())? {
                 if let Event::Key(key_event) = event::read()? {
-                    match key_event.code {
-                        KeyCode::Char('q') | KeyCode::Esc => break 'gameloop,
-                        KeyCode::Left | KeyCode::Char('a') => player.move_left(),
-                        KeyCode::Right | KeyCode::Char('d') => player.move_right(),
-                        KeyCode::Char(' ') | KeyCode::Enter => {
-                            if player.shoot() {
-                                audio.play("pew");
-                            }
-                        }
-                        _ => {}
+                    if volume_key(key_event.code, &mut sound_effects.sound) {
+                        continue;
                     }
+                    started = !matches!(key_event.code, KeyCode::Char('q') | KeyCode::Esc);
+                    break 'title;
                 }
             }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // the wait doesn't count as time played
+        instant = Instant::now();
+    }
 
-            // Update
-            let mut updates: Vec<&mut dyn Updatable> = vec![&mut player, &mut invaders];
-            for update in &mut updates {
-                update.update(delta);
-            }
+    'gameloop: loop {
+        // quitting on the title screen skips the game
+        if !started {
+            break 'gameloop;
+        }
+
+        let delta = instant.elapsed();
+        instant = Instant::now();
+        sound_effects.sound.next_tick();
+
+        let mut layers = Layers::new();
 
-            // Collision detection
-            for shot in &mut player.shots {
-                if let Some(score) = invaders.check_collision(shot) {
-                    status.add_score(score);
-                    shot.explode();
-                    audio.play("boom");
+        // Input, the volume keys and quitting work even while the cannon is gone
+        while event::poll(Duration::default())? {
+            if let Event::Key(key_event) = event::read()? {
+                if volume_key(key_event.code, &mut sound_effects.sound) {
+                    continue;
                 }
-            }
 
-            for shot in &mut invaders.shots {
-                if player.check_collision(shot) {
-                    audio.play("boom");
-                    shot.explode();
-                    match player.die() {
-                        None => break 'gameloop,
-                        Some(lives) => {
-                            death_timer.reset();
-                            status.update_lives(lives);
-                        }
+                if autopilot.is_some() {
+                    break 'gameloop;
+                }
+                if matches!(key_event.code, KeyCode::Char('q') | KeyCode::Esc) {
+                    break 'gameloop;
+                }
+                if game.player.dead() {
+                    continue;
+                }
+
+                // in versus mode the arrows and enter belong to the invader commander
+                if game.army.commanded() {
+                    let command = match key_event.code {
+                        KeyCode::Left => Some(Command::PrevColumn),
+                        KeyCode::Right => Some(Command::NextColumn),
+                        KeyCode::Up => Some(Command::Faster),
+                        KeyCode::Down => Some(Command::Slower),
+                        KeyCode::Enter => Some(Command::Fire),
+                        _ => None,
+                    };
+                    if let Some(command) = command {
+                        game.army.command(command);
+                        continue;
                     }
                 }
+
+                match key_event.code {
+                    KeyCode::Left | KeyCode::Char('a') => game.act(Action::Left),
+                    KeyCode::Right | KeyCode::Char('d') => game.act(Action::Right),
+                    KeyCode::Char(' ') | KeyCode::Enter => game.act(Action::Fire),
+                    _ => {}
+                }
             }
-        } else {
-            death_timer.update(delta);
-            if death_timer.ready {
-                player.resurrect();
+        }
+
+        if !game.player.dead() {
+            if let Some(bot) = &mut autopilot {
+                bot_timer.update(delta);
+                if bot_timer.ready {
+                    let action = bot.act(&game.player, &game.army);
+                    game.act(action);
+                    bot_timer.reset();
+                }
             }
         }
 
-        if invaders.invaded() {
+        // Update, the camera may hold the game still for a moment
+        camera.update(delta);
+        let game_delta = camera.game_time(delta);
+        game.update(game_delta);
+
+        let events = game.events.publish(&mut [
+            &mut sound_effects,
+            &mut achievements,
+            &mut particles,
+            &mut camera,
+        ]);
+
+        // the cannon finishes blowing up before the game ends
+        let over = game.game_over().is_some();
+        if over && !game.player.exploding() {
             break 'gameloop;
         }
 
-        if invaders.all_dead() {
+        if events
+            .iter()
+            .any(|event| matches!(event, events::Event::LevelCleared { .. }))
+        {
+            army_size = game.army.invaders.len();
             // wait for a bit
             tokio::time::sleep(Duration::from_millis(1000)).await;
-            status.level_up();
-            invaders = Army::new(status.level);
-            player.reset_lives();
-            status.update_lives(player.lives);
-            player.shots.clear();
-            invaders.shots.clear();
+        }
+
+        // the soundtrack speeds up as the army thins out
+        if !over {
+            let alive = game.army.invaders.len() as f32 / army_size.max(1) as f32;
+            sound_effects
+                .music
+                .set_tempo(1.0 + MAX_TEMPO_BOOST * (1.0 - alive));
+            sound_effects.set_scene(if game.army.rows_left() <= DANGER_ROWS {
+                Scene::Boss
+            } else {
+                Scene::Gameplay
+            });
         }
 
         // Draw
-        let drawables: Vec<&dyn Drawable> = vec![&player, &invaders, &status];
-        for drawable in drawables {
-            drawable.draw(&mut frame);
+        if let Some(starfield) = &mut starfield {
+            starfield.update(game_delta);
+            starfield.draw(layers.get_mut(Layer::Background));
         }
+        options.renderer.draw(&game, &mut layers, &context);
+        particles.update(game_delta);
+        particles.draw_layers(&mut layers);
+        let mut frame = new_frame();
+        layers.flatten_shifted(&mut frame, camera.offset());
+        let image = options.renderer.image(&game, &context);
 
         // Render
-        let _ = render_tx.send(frame).await;
+        let _ = render_tx.send((frame, image)).await;
 
         tokio::time::sleep(Duration::from_millis(1)).await;
     }
 
     drop(render_tx);
     let _ = tokio::join!(render_handle);
-    audio.wait();
+
+    let mut new_settings = settings.clone();
+    new_settings.update_from(sound_effects.sound.mixer());
+    if new_settings != settings {
+        let _ = new_settings.save();
+    }
+
     stdout.execute(crossterm::cursor::Show)?;
     stdout.execute(terminal::LeaveAlternateScreen)?;
     terminal::disable_raw_mode()?;
 
+    if !achievements.unlocked.is_empty() {
+        let names: Vec<&str> = achievements.unlocked.iter().map(|a| a.name()).collect();
+        println!("Achievements unlocked: {}", names.join(", "));
+    }
+
+    // the game over tune gets to finish, one that loops is cut off
+    if sound_effects.music.scene() != Some(Scene::GameOver) {
+        sound_effects.music.stop(&mut sound_effects.sound);
+    }
+    sound_effects.sound.wait(EXIT_WAIT);
+
     Ok(())
 }
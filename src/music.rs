@@ -0,0 +1,1000 @@
+use std::{
+    collections::HashMap,
+    f32::consts::TAU,
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rodio::Source;
+
+use crate::{assets::parse_word, sound::Sound};
+
+/// Music is rendered in mono at this rate
+pub const SAMPLE_RATE: u32 = 22050;
+
+// Note number of C-4, the pitch samples play back at their own rate
+const MIDDLE_C: f32 = 48.0;
+// Note number of A-4, 440 Hz
+const CONCERT_A: f32 = 57.0;
+// Rate a MOD sample plays at for C-4
+const AMIGA_C4_RATE: f32 = 8363.0;
+// Amiga PAL clock, converts MOD periods to sample rates
+const AMIGA_CLOCK: f32 = 7_093_789.2;
+const MOD_ROWS: usize = 64;
+
+/// Parts of the game that get their own tune
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scene {
+    /// Waiting for the player to start
+    Title,
+    Gameplay,
+    /// The invaders are about to land
+    Boss,
+    GameOver,
+}
+
+impl Scene {
+    pub const ALL: [Scene; 4] = [Scene::Title, Scene::Gameplay, Scene::Boss, Scene::GameOver];
+
+    /// Music files for a scene are named after it, e.g. `gameplay.mod`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scene::Title => "title",
+            Scene::Gameplay => "gameplay",
+            Scene::Boss => "boss",
+            Scene::GameOver => "gameover",
+        }
+    }
+}
+
+/// The built-in tunes, in the pattern format described on `Song::parse`
+pub const SONGS: [(Scene, &str); 4] = [
+    (Scene::Title, include_str!("../assets/music/title.song")),
+    (
+        Scene::Gameplay,
+        include_str!("../assets/music/gameplay.song"),
+    ),
+    (Scene::Boss, include_str!("../assets/music/boss.song")),
+    (
+        Scene::GameOver,
+        include_str!("../assets/music/gameover.song"),
+    ),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wave {
+    Square,
+    Saw,
+    Triangle,
+    Sine,
+    Noise,
+}
+
+pub enum Instrument {
+    /// Generated on the fly, fades out over `decay` seconds (0 holds the note)
+    Synth { wave: Wave, volume: f32, decay: f32 },
+    /// Signed samples from a module, with an optional loop
+    Sampled {
+        data: Vec<f32>,
+        volume: f32,
+        loop_start: usize,
+        loop_len: usize,
+        /// Semitones added to every note played with it
+        transpose: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Note {
+    /// A note number, 48 is C-4
+    On(f32),
+    Off,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect {
+    /// Ticks per row
+    Speed(u32),
+    Bpm(u32),
+    /// Continue at this position in the order list
+    Jump(usize),
+    /// Continue at this row of the next pattern
+    Break(usize),
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Cell {
+    pub note: Option<Note>,
+    pub instrument: Option<usize>,
+    /// 0.0 to 1.0
+    pub volume: Option<f32>,
+    pub effect: Option<Effect>,
+}
+
+/// Rows of cells, one cell per channel
+pub type Pattern = Vec<Vec<Cell>>;
+
+pub struct Song {
+    pub name: String,
+    pub channels: usize,
+    pub instruments: Vec<Instrument>,
+    pub patterns: Vec<Pattern>,
+    /// Patterns in the order they are played
+    pub order: Vec<usize>,
+    pub speed: u32,
+    pub bpm: u32,
+    /// Start over at the end, otherwise the song stops
+    pub looping: bool,
+}
+
+impl Song {
+    /// Loads a `.mod`, `.xm` or `.song` file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match extension.as_str() {
+            "mod" => Self::parse_mod(&fs::read(path)?),
+            "song" => Self::parse(&fs::read_to_string(path)?),
+            "xm" => Self::parse_xm(&fs::read(path)?),
+            _ => Err(format!("Unknown music format: {}", path.display()).into()),
+        }
+    }
+
+    /// Parses the built-in pattern format:
+    ///
+    /// ```text
+    /// # comments start with a hash after whitespace
+    /// name Gameplay
+    /// bpm 125
+    /// speed 6
+    /// loop yes
+    /// instrument 1 square 0.3 0.2   # id, wave, volume, decay seconds
+    /// pattern 0
+    /// C-3:1  ...                    # one row per line, one cell per channel
+    /// off    G#3:1
+    /// order 0 0
+    /// ```
+    ///
+    /// A cell is `...` for nothing, `off` to stop the note, or a note with an
+    /// optional instrument, e.g. `C-3:1`. Waves are `square`, `saw`,
+    /// `triangle`, `sine` and `noise`.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut song = Song {
+            name: String::new(),
+            channels: 0,
+            instruments: Vec::new(),
+            patterns: Vec::new(),
+            order: Vec::new(),
+            speed: 6,
+            bpm: 125,
+            looping: true,
+        };
+        let mut instrument_ids = HashMap::new();
+        let mut pattern_ids = HashMap::new();
+        let mut order_ids = Vec::new();
+        let mut current_pattern = None;
+
+        for (number, line) in text.lines().enumerate() {
+            // a hash inside a note is a sharp, comments start after whitespace
+            let line = match line.find(" #").or(line.starts_with('#').then_some(0)) {
+                Some(at) => &line[..at],
+                None => line,
+            }
+            .trim();
+            let mut words = line.split_whitespace();
+            let Some(keyword) = words.next() else {
+                continue;
+            };
+            let error = |message: &str| format!("Line {}: {}", number + 1, message);
+
+            match keyword {
+                "name" => song.name = words.collect::<Vec<_>>().join(" "),
+                "bpm" => song.bpm = parse_word(words.next()).ok_or_else(|| error("bad bpm"))?,
+                "speed" => {
+                    song.speed = parse_word(words.next()).ok_or_else(|| error("bad speed"))?
+                }
+                "loop" => song.looping = words.next() != Some("no"),
+                "instrument" => {
+                    let id: String = words.next().ok_or_else(|| error("missing id"))?.into();
+                    let wave = match words.next() {
+                        Some("square") => Wave::Square,
+                        Some("saw") => Wave::Saw,
+                        Some("triangle") => Wave::Triangle,
+                        Some("sine") => Wave::Sine,
+                        Some("noise") => Wave::Noise,
+                        _ => return Err(error("unknown wave").into()),
+                    };
+                    let volume = parse_word(words.next()).unwrap_or(0.5);
+                    let decay = parse_word(words.next()).unwrap_or(0.0);
+                    instrument_ids.insert(id, song.instruments.len());
+                    song.instruments.push(Instrument::Synth {
+                        wave,
+                        volume,
+                        decay,
+                    });
+                }
+                "pattern" => {
+                    let id: String = words.next().ok_or_else(|| error("missing id"))?.into();
+                    pattern_ids.insert(id, song.patterns.len());
+                    current_pattern = Some(song.patterns.len());
+                    song.patterns.push(Vec::new());
+                }
+                "order" => order_ids.extend(words.map(str::to_string)),
+                _ => {
+                    let pattern = current_pattern.ok_or_else(|| error("row outside a pattern"))?;
+                    let row = line
+                        .split_whitespace()
+                        .map(|cell| parse_cell(cell, &instrument_ids))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| error("bad cell"))?;
+                    song.channels = song.channels.max(row.len());
+                    song.patterns[pattern].push(row);
+                }
+            }
+        }
+
+        song.order = order_ids
+            .iter()
+            .map(|id| pattern_ids.get(id).copied())
+            .collect::<Option<_>>()
+            .ok_or("Order refers to a missing pattern")?;
+
+        if song.order.is_empty() {
+            song.order = (0..song.patterns.len()).collect();
+        }
+        if song.order.is_empty() || song.bpm == 0 || song.speed == 0 {
+            return Err("Song has nothing to play".into());
+        }
+
+        Ok(song)
+    }
+
+    /// Parses a ProTracker module. Notes, samples, loops and the set volume,
+    /// speed, position jump and pattern break effects are played, other
+    /// effects are ignored.
+    pub fn parse_mod(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        if bytes.len() < 1084 {
+            return Err("File is too short to be a module".into());
+        }
+
+        let channels = match &bytes[1080..1084] {
+            b"M.K." | b"M!K!" | b"FLT4" | b"4CHN" => 4,
+            b"6CHN" => 6,
+            b"8CHN" | b"FLT8" => 8,
+            _ => return Err("Unsupported module type".into()),
+        };
+
+        let word = |at: usize| u16::from_be_bytes([bytes[at], bytes[at + 1]]) as usize;
+
+        let mut samples = Vec::new();
+        for i in 0..31 {
+            let at = 20 + i * 30;
+            samples.push((
+                word(at + 22) * 2,
+                (bytes[at + 25].min(64)) as f32 / 64.0,
+                word(at + 26) * 2,
+                word(at + 28) * 2,
+            ));
+        }
+
+        let song_len = (bytes[950] as usize).clamp(1, 128);
+        let order: Vec<usize> = bytes[952..952 + song_len]
+            .iter()
+            .map(|&p| p as usize)
+            .collect();
+        let num_patterns = bytes[952..1080].iter().copied().max().unwrap_or(0) as usize + 1;
+
+        let pattern_size = MOD_ROWS * channels * 4;
+        let mut at = 1084;
+        let mut patterns = Vec::new();
+        for _ in 0..num_patterns {
+            let data = bytes
+                .get(at..at + pattern_size)
+                .ok_or("Module ends inside a pattern")?;
+            let rows = data
+                .chunks(channels * 4)
+                .map(|row| row.chunks(4).map(parse_mod_cell).collect())
+                .collect();
+            patterns.push(rows);
+            at += pattern_size;
+        }
+
+        let mut instruments = Vec::new();
+        for (len, volume, loop_start, loop_len) in samples {
+            // modules in the wild are sometimes cut short, play what's there
+            let end = (at + len).min(bytes.len());
+            let data = bytes[at.min(end)..end]
+                .iter()
+                .map(|&b| b as i8 as f32 / 128.0)
+                .collect::<Vec<_>>();
+            at += len;
+
+            let loop_len = if loop_len > 2 && loop_start + loop_len <= data.len() {
+                loop_len
+            } else {
+                0
+            };
+            instruments.push(Instrument::Sampled {
+                data,
+                volume,
+                loop_start,
+                loop_len,
+                transpose: 0.0,
+            });
+        }
+
+        let name = String::from_utf8_lossy(&bytes[..20])
+            .trim_end_matches('\0')
+            .trim()
+            .to_string();
+
+        Ok(Song {
+            name,
+            channels,
+            instruments,
+            patterns,
+            order,
+            speed: 6,
+            bpm: 125,
+            looping: true,
+        })
+    }
+
+    /// Parses a FastTracker II module. The same notes and effects as
+    /// `parse_mod` are played, plus the volume column. Instruments play
+    /// their first sample only and ping-pong loops loop forwards.
+    pub fn parse_xm(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        if !bytes.starts_with(b"Extended Module: ") {
+            return Err("Not an XM module".into());
+        }
+
+        let byte = |at: usize| bytes.get(at).copied().ok_or("Module is cut short");
+        let word = |at: usize| -> Result<usize, &str> {
+            Ok(u16::from_le_bytes([byte(at)?, byte(at + 1)?]) as usize)
+        };
+        let long = |at: usize| -> Result<usize, &str> { Ok(word(at)? | word(at + 2)? << 16) };
+
+        let song_len = word(64)?.clamp(1, 256);
+        let channels = word(68)?;
+        let num_patterns = word(70)?;
+        let num_instruments = word(72)?;
+        if channels == 0 || channels > 32 {
+            return Err("Unsupported number of channels".into());
+        }
+        let order = (0..song_len)
+            .map(|i| byte(80 + i).map(|p| p as usize))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut at = 60 + long(60)?;
+        let mut patterns = Vec::new();
+        for _ in 0..num_patterns {
+            let rows = word(at + 5)?;
+            let packed_len = word(at + 7)?;
+            at += long(at)?;
+            let data = bytes
+                .get(at..at + packed_len)
+                .ok_or("Module ends inside a pattern")?;
+            at += packed_len;
+
+            let mut data = data.iter().copied();
+            let mut pattern = Vec::new();
+            for _ in 0..rows {
+                let mut row = Vec::new();
+                for _ in 0..channels {
+                    let mut next = || data.next().ok_or("Module ends inside a pattern");
+                    // an empty pattern has no data at all
+                    let first = if packed_len == 0 { 0x80 } else { next()? };
+                    // a set high bit says which of the five fields follow
+                    let mut fields = [0; 5];
+                    for (i, field) in fields.iter_mut().enumerate() {
+                        if first & 0x80 == 0 {
+                            *field = if i == 0 { first } else { next()? };
+                        } else if first & (1 << i) != 0 {
+                            *field = next()?;
+                        }
+                    }
+                    row.push(parse_xm_cell(fields));
+                }
+                pattern.push(row);
+            }
+            patterns.push(pattern);
+        }
+
+        let mut instruments = Vec::new();
+        for _ in 0..num_instruments {
+            let num_samples = word(at + 27)?;
+            let header_len = if num_samples > 0 { long(at + 29)? } else { 0 };
+            at += long(at)?;
+
+            let mut headers = Vec::new();
+            for _ in 0..num_samples {
+                headers.push((
+                    long(at)?,
+                    long(at + 4)?,
+                    long(at + 8)?,
+                    byte(at + 12)?.min(64) as f32 / 64.0,
+                    byte(at + 13)? as i8,
+                    byte(at + 14)?,
+                    byte(at + 16)? as i8,
+                ));
+                at += header_len;
+            }
+
+            let mut samples = Vec::new();
+            for (len, loop_start, loop_len, volume, finetune, kind, relative) in headers {
+                // like MODs, play what's there of a cut short sample
+                let end = (at + len).min(bytes.len());
+                let raw = &bytes[at.min(end)..end];
+                at += len;
+
+                // samples are stored as differences from the previous one
+                let wide = kind & 0x10 != 0;
+                let data = if wide {
+                    raw.chunks_exact(2)
+                        .scan(0i16, |last, pair| {
+                            *last = last.wrapping_add(i16::from_le_bytes([pair[0], pair[1]]));
+                            Some(*last as f32 / 32768.0)
+                        })
+                        .collect::<Vec<_>>()
+                } else {
+                    raw.iter()
+                        .scan(0i8, |last, &b| {
+                            *last = last.wrapping_add(b as i8);
+                            Some(*last as f32 / 128.0)
+                        })
+                        .collect()
+                };
+
+                let scale = if wide { 2 } else { 1 };
+                let (loop_start, loop_len) = (loop_start / scale, loop_len / scale);
+                let loop_len =
+                    if kind & 0x3 != 0 && loop_len > 0 && loop_start + loop_len <= data.len() {
+                        loop_len
+                    } else {
+                        0
+                    };
+                samples.push(Instrument::Sampled {
+                    data,
+                    volume,
+                    loop_start,
+                    loop_len,
+                    transpose: relative as f32 + finetune as f32 / 128.0,
+                });
+            }
+
+            instruments.push(samples.into_iter().next().unwrap_or(Instrument::Sampled {
+                data: Vec::new(),
+                volume: 0.0,
+                loop_start: 0,
+                loop_len: 0,
+                transpose: 0.0,
+            }));
+        }
+
+        let name = String::from_utf8_lossy(&bytes[17..37])
+            .trim_end_matches('\0')
+            .trim()
+            .to_string();
+
+        Ok(Song {
+            name,
+            channels,
+            instruments,
+            patterns,
+            order,
+            speed: word(76)?.max(1) as u32,
+            bpm: word(78)?.max(1) as u32,
+            looping: true,
+        })
+    }
+}
+
+fn parse_cell(cell: &str, instruments: &HashMap<String, usize>) -> Option<Cell> {
+    if cell.chars().all(|c| c == '.') {
+        return Some(Cell::default());
+    }
+    if cell == "off" {
+        return Some(Cell {
+            note: Some(Note::Off),
+            ..Cell::default()
+        });
+    }
+
+    let (note, instrument) = match cell.split_once(':') {
+        Some((note, instrument)) => (note, Some(*instruments.get(instrument)?)),
+        None => (cell, None),
+    };
+
+    let mut chars = note.chars();
+    let semitone = match chars.next()? {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let sharp = match chars.next()? {
+        '#' => 1,
+        '-' => 0,
+        _ => return None,
+    };
+    let octave: i32 = chars.as_str().parse().ok()?;
+
+    Some(Cell {
+        note: Some(Note::On((octave * 12 + semitone + sharp) as f32)),
+        instrument,
+        ..Cell::default()
+    })
+}
+
+fn parse_mod_cell(data: &[u8]) -> Cell {
+    let sample = ((data[0] & 0xF0) | (data[2] >> 4)) as usize;
+    let period = (((data[0] & 0x0F) as usize) << 8) | data[1] as usize;
+    let param = data[3] as u32;
+
+    let mut cell = Cell {
+        instrument: sample.checked_sub(1),
+        ..Cell::default()
+    };
+
+    if period > 0 {
+        let rate = AMIGA_CLOCK / (2.0 * period as f32);
+        cell.note = Some(Note::On(MIDDLE_C + 12.0 * (rate / AMIGA_C4_RATE).log2()));
+    }
+
+    parse_effect(&mut cell, data[2] & 0x0F, param);
+    cell
+}
+
+// fields are note, instrument, volume column, effect and its parameter
+fn parse_xm_cell([note, instrument, volume, effect, param]: [u8; 5]) -> Cell {
+    let mut cell = Cell {
+        note: match note {
+            1..=96 => Some(Note::On((note - 1) as f32)),
+            97 => Some(Note::Off),
+            _ => None,
+        },
+        instrument: (instrument as usize).checked_sub(1),
+        ..Cell::default()
+    };
+
+    if (0x10..=0x50).contains(&volume) {
+        cell.volume = Some((volume - 0x10) as f32 / 64.0);
+    }
+
+    parse_effect(&mut cell, effect, param as u32);
+    cell
+}
+
+// MOD and XM share the effects we play
+fn parse_effect(cell: &mut Cell, effect: u8, param: u32) {
+    match effect {
+        0xB => cell.effect = Some(Effect::Jump(param as usize)),
+        0xC => cell.volume = Some(param.min(64) as f32 / 64.0),
+        // the break row is stored as two decimal digits
+        0xD => cell.effect = Some(Effect::Break(((param >> 4) * 10 + (param & 0xF)) as usize)),
+        0xF if param == 0 => {}
+        0xF if param < 32 => cell.effect = Some(Effect::Speed(param)),
+        0xF => cell.effect = Some(Effect::Bpm(param)),
+        _ => {}
+    }
+}
+
+#[derive(Default)]
+struct Channel {
+    instrument: Option<usize>,
+    playing: bool,
+    note: f32,
+    volume: f32,
+    // position in the wave (0..1) or sample (in samples)
+    position: f32,
+    // seconds since the note started
+    age: f32,
+}
+
+/// Plays a `Song` as a rodio source. The tempo can be changed from another
+/// thread while it plays.
+pub struct SongPlayer {
+    song: Arc<Song>,
+    tempo: Arc<AtomicU32>,
+    channels: Vec<Channel>,
+    order: usize,
+    row: usize,
+    tick: u32,
+    speed: u32,
+    bpm: u32,
+    samples_to_tick: f32,
+    noise: StdRng,
+    finished: bool,
+}
+
+impl SongPlayer {
+    pub fn new(song: Arc<Song>, tempo: Arc<AtomicU32>) -> Self {
+        let channels = (0..song.channels).map(|_| Channel::default()).collect();
+        Self {
+            speed: song.speed,
+            bpm: song.bpm,
+            song,
+            tempo,
+            channels,
+            order: 0,
+            row: 0,
+            tick: 0,
+            samples_to_tick: 0.0,
+            noise: StdRng::seed_from_u64(0),
+            finished: false,
+        }
+    }
+
+    fn play_row(&mut self) {
+        let song = self.song.clone();
+        let Some(pattern) = song
+            .order
+            .get(self.order)
+            .and_then(|&p| song.patterns.get(p))
+        else {
+            self.finished = true;
+            return;
+        };
+
+        let mut next = None;
+        if let Some(row) = pattern.get(self.row) {
+            for (channel, cell) in self.channels.iter_mut().zip(row) {
+                if let Some(instrument) = cell.instrument {
+                    channel.instrument = Some(instrument);
+                    channel.volume = match song.instruments.get(instrument) {
+                        Some(Instrument::Synth { volume, .. })
+                        | Some(Instrument::Sampled { volume, .. }) => *volume,
+                        None => 0.0,
+                    };
+                }
+                match cell.note {
+                    Some(Note::On(note)) => {
+                        channel.note = note;
+                        channel.position = 0.0;
+                        channel.age = 0.0;
+                        channel.playing = channel.instrument.is_some();
+                    }
+                    Some(Note::Off) => channel.playing = false,
+                    None => {}
+                }
+                if let Some(volume) = cell.volume {
+                    channel.volume = volume;
+                }
+                match cell.effect {
+                    Some(Effect::Speed(speed)) => self.speed = speed.max(1),
+                    Some(Effect::Bpm(bpm)) => self.bpm = bpm.max(1),
+                    Some(Effect::Jump(order)) => next = Some((order, 0)),
+                    Some(Effect::Break(row)) => {
+                        next = Some((next.map_or(self.order + 1, |(o, _)| o), row))
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        let (order, row) = next.unwrap_or(if self.row + 1 >= pattern.len() {
+            (self.order + 1, 0)
+        } else {
+            (self.order, self.row + 1)
+        });
+
+        // a jump backwards means the song loops on its own
+        if order >= song.order.len() || (next.is_some() && order <= self.order && !song.looping) {
+            if song.looping {
+                self.order = 0;
+                self.row = 0;
+            } else {
+                self.order = song.order.len();
+            }
+        } else {
+            self.order = order;
+            self.row = row;
+        }
+    }
+
+    fn tempo(&self) -> f32 {
+        f32::from_bits(self.tempo.load(Ordering::Relaxed)).clamp(0.25, 4.0)
+    }
+
+    fn mix(&mut self) -> f32 {
+        let song = &self.song;
+        let mut out = 0.0;
+
+        for channel in &mut self.channels {
+            if !channel.playing {
+                continue;
+            }
+            let Some(instrument) = channel.instrument.and_then(|i| song.instruments.get(i)) else {
+                continue;
+            };
+
+            match instrument {
+                Instrument::Synth { wave, decay, .. } => {
+                    let envelope = if *decay > 0.0 {
+                        1.0 - channel.age / decay
+                    } else {
+                        1.0
+                    };
+                    if envelope <= 0.0 {
+                        channel.playing = false;
+                        continue;
+                    }
+
+                    let phase = channel.position.fract();
+                    let value = match wave {
+                        Wave::Square => {
+                            if phase < 0.5 {
+                                1.0
+                            } else {
+                                -1.0
+                            }
+                        }
+                        Wave::Saw => phase * 2.0 - 1.0,
+                        Wave::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+                        Wave::Sine => (TAU * phase).sin(),
+                        Wave::Noise => self.noise.gen_range(-1.0..1.0),
+                    };
+                    out += value * channel.volume * envelope;
+
+                    let freq = 440.0 * 2f32.powf((channel.note - CONCERT_A) / 12.0);
+                    channel.position += freq / SAMPLE_RATE as f32;
+                }
+                Instrument::Sampled {
+                    data,
+                    loop_start,
+                    loop_len,
+                    transpose,
+                    ..
+                } => {
+                    let index = channel.position as usize;
+                    let Some(value) = data.get(index) else {
+                        channel.playing = false;
+                        continue;
+                    };
+                    out += value * channel.volume;
+
+                    let rate =
+                        AMIGA_C4_RATE * 2f32.powf((channel.note + transpose - MIDDLE_C) / 12.0);
+                    channel.position += rate / SAMPLE_RATE as f32;
+                    if *loop_len > 0 && channel.position >= (loop_start + loop_len) as f32 {
+                        channel.position -= *loop_len as f32;
+                    }
+                }
+            }
+
+            channel.age += 1.0 / SAMPLE_RATE as f32;
+        }
+
+        // keep a full band of channels from clipping
+        out / (self.channels.len().max(1) as f32).sqrt()
+    }
+}
+
+impl Iterator for SongPlayer {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.samples_to_tick <= 0.0 {
+            if self.tick == 0 {
+                self.play_row();
+            }
+            self.tick = (self.tick + 1) % self.speed;
+
+            // trackers run 2.5 / bpm seconds per tick
+            let tick_secs = 2.5 / self.bpm as f32 / self.tempo();
+            self.samples_to_tick += tick_secs * SAMPLE_RATE as f32;
+        }
+
+        if self.finished && self.channels.iter().all(|c| !c.playing) {
+            return None;
+        }
+
+        self.samples_to_tick -= 1.0;
+        let value = self.mix().clamp(-1.0, 1.0);
+        Some((value * i16::MAX as f32) as i16)
+    }
+}
+
+impl Source for SongPlayer {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// The soundtrack: one song per scene, played through `Sound` next to the
+/// effects.
+pub struct Music {
+    songs: HashMap<Scene, Arc<Song>>,
+    scene: Option<Scene>,
+    tempo: Arc<AtomicU32>,
+}
+
+impl Music {
+    /// A soundtrack without any songs
+    pub fn silent() -> Self {
+        Self {
+            songs: HashMap::new(),
+            scene: None,
+            tempo: Arc::new(AtomicU32::new(1.0f32.to_bits())),
+        }
+    }
+
+    /// The built-in soundtrack
+    pub fn new() -> Self {
+        let mut music = Self::silent();
+        for (scene, text) in SONGS {
+            if let Ok(song) = Song::parse(text) {
+                music.set_song(scene, song);
+            }
+        }
+        music
+    }
+
+    pub fn set_song(&mut self, scene: Scene, song: Song) {
+        self.songs.insert(scene, Arc::new(song));
+    }
+
+    /// Replaces songs with `<scene>.mod`, `<scene>.xm` or `<scene>.song`
+    /// files from `dir`. Files that can't be loaded are skipped with a
+    /// warning and the song they were meant to replace stays.
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        for scene in Scene::ALL {
+            for extension in ["mod", "song", "xm"] {
+                let path = dir.as_ref().join(format!("{}.{}", scene.name(), extension));
+                if !path.is_file() {
+                    continue;
+                }
+                match Song::load(&path) {
+                    Ok(song) => self.set_song(scene, song),
+                    Err(e) => eprintln!("Skipping music file {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+
+    pub fn scene(&self) -> Option<Scene> {
+        self.scene
+    }
+
+    /// Switches to the song for `scene`, keeps playing if it's already on
+    pub fn play(&mut self, scene: Scene, sound: &mut Sound) {
+        if self.scene == Some(scene) {
+            return;
+        }
+        self.scene = Some(scene);
+
+        match self.songs.get(&scene) {
+            Some(song) => sound.play_music(
+                scene.name(),
+                Box::new(SongPlayer::new(song.clone(), self.tempo.clone())),
+            ),
+            None => sound.stop_music(),
+        }
+    }
+
+    pub fn stop(&mut self, sound: &mut Sound) {
+        self.scene = None;
+        sound.stop_music();
+    }
+
+    /// 1.0 is the song's own tempo
+    pub fn set_tempo(&self, tempo: f32) {
+        self.tempo.store(tempo.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl Default for Music {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two channels, one pattern of two rows, one instrument with a looped
+    // 8-bit sample
+    fn tiny_xm() -> Vec<u8> {
+        let mut xm = b"Extended Module: Tiny".to_vec();
+        xm.resize(60, 0);
+        xm.extend(276u32.to_le_bytes());
+        for value in [1u16, 0, 2, 1, 1, 1, 5, 140] {
+            xm.extend(value.to_le_bytes());
+        }
+        xm.resize(336, 0);
+
+        // a packed C-4 with instrument 1 at volume 32, a plain key off with
+        // speed 3, then an empty row
+        let packed = [&[0x87, 49, 1, 0x30][..], &[97, 0, 0, 0xF, 3], &[0x80, 0x80]].concat();
+        xm.extend(9u32.to_le_bytes());
+        xm.push(0);
+        xm.extend(2u16.to_le_bytes());
+        xm.extend((packed.len() as u16).to_le_bytes());
+        xm.extend(&packed);
+
+        let start = xm.len();
+        xm.extend(33u32.to_le_bytes());
+        xm.resize(start + 27, 0);
+        xm.extend(1u16.to_le_bytes());
+        xm.extend(40u32.to_le_bytes());
+        xm.resize(start + 33, 0);
+
+        // four samples looping over the last two, an octave up
+        for value in [4u32, 2, 2] {
+            xm.extend(value.to_le_bytes());
+        }
+        xm.extend([64, 0, 1, 0x80, 12, 0]);
+        xm.resize(xm.len() + 22, 0);
+        xm.extend([10, 10, 0xF6, 0xF6]);
+        xm
+    }
+
+    #[test]
+    fn parses_an_xm_module() {
+        let song = Song::parse_xm(&tiny_xm()).unwrap();
+        assert_eq!(song.name, "Tiny");
+        assert_eq!((song.channels, song.speed, song.bpm), (2, 5, 140));
+        assert_eq!(song.order, vec![0]);
+
+        let rows = &song.patterns[0];
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][0].note, Some(Note::On(MIDDLE_C)));
+        assert_eq!(rows[0][0].instrument, Some(0));
+        assert_eq!(rows[0][0].volume, Some(0.5));
+        assert_eq!(rows[0][1].note, Some(Note::Off));
+        assert_eq!(rows[0][1].effect, Some(Effect::Speed(3)));
+        assert!(rows[1].iter().all(|cell| cell.note.is_none()));
+
+        let Instrument::Sampled {
+            data,
+            volume,
+            loop_start,
+            loop_len,
+            transpose,
+        } = &song.instruments[0]
+        else {
+            panic!("expected a sampled instrument");
+        };
+        let expected = [10.0, 20.0, 10.0, 0.0].map(|v: f32| v / 128.0);
+        assert_eq!(data.as_slice(), expected.as_slice());
+        assert_eq!(
+            (*volume, *loop_start, *loop_len, *transpose),
+            (1.0, 2, 2, 12.0)
+        );
+    }
+
+    #[test]
+    fn cut_short_xm_modules_are_rejected() {
+        let xm = tiny_xm();
+        assert!(Song::parse_xm(&xm[..300]).is_err());
+        assert!(Song::parse_xm(b"not a module").is_err());
+    }
+}
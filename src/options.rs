@@ -0,0 +1,208 @@
+use std::env;
+
+use crate::{
+    army::Tuning, colors::ColorDepth, external, glyphs::GlyphSet, player, render::Renderer,
+};
+
+// Marching any faster is more than anyone can play against
+const MIN_MARCH_MS: u64 = 100;
+
+/// Command line options. Everything is optional, running without arguments
+/// starts a regular single player game.
+pub struct Options {
+    /// Address to broadcast frames on for spectators, e.g. `127.0.0.1:4000`
+    pub broadcast: Option<String>,
+    /// Address of a running game to watch instead of playing
+    pub watch: Option<String>,
+    /// Two players on one keyboard, the second one commands the invaders
+    pub versus: bool,
+    /// Let the built-in autopilot play, any key quits
+    pub autoplay: bool,
+    /// External bot programs, see `external::ExternalBot`
+    pub bots: Vec<String>,
+    /// Milliseconds an external bot gets to answer each tick
+    pub bot_timeout: u64,
+    /// Rank the external bots against each other instead of playing
+    pub tournament: bool,
+    /// Number of seeded games to play in a tournament or simulation
+    pub seeds: Option<u64>,
+    /// Run the `simulate` subcommand instead of a game
+    pub simulate: bool,
+    /// Built-in player used by `simulate`, `autopilot` or `sweeper`
+    pub player: String,
+    pub tuning: Tuning,
+    /// How long the cannon can't be hit after coming back, 0 for not at all
+    pub invulnerable_ms: u64,
+    /// Write the built-in sound effects to this directory and exit
+    pub export_sfx: Option<String>,
+    /// Directory with sound files replacing the embedded defaults
+    pub sfx: Option<String>,
+    /// Don't even try to open the audio device
+    pub no_sound: bool,
+    /// Directory with `<scene>.mod` or `<scene>.song` files replacing the built-in music
+    pub music: Option<String>,
+    /// Keep the sound effects but leave out the soundtrack
+    pub no_music: bool,
+    /// Force a glyph set instead of going by the locale
+    pub glyphs: Option<GlyphSet>,
+    /// Force a colour depth instead of asking the terminal
+    pub colors: Option<ColorDepth>,
+    /// Directory with `.sprites` files replacing the built-in sprites
+    pub sprites: Option<String>,
+    /// Theme for this run instead of the one in the settings
+    pub theme: Option<String>,
+    pub renderer: Renderer,
+    /// Leave out the starfield, whatever the settings say
+    pub no_stars: bool,
+    /// Don't light up the screen when the cannon is hit, whatever the settings say
+    pub no_flash: bool,
+    /// Screen shake in percent instead of the one in the settings, 0 turns it off
+    pub shake: Option<u8>,
+    /// Hit-stop in milliseconds instead of the one in the settings, 0 turns it off
+    pub hit_stop: Option<u64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            broadcast: None,
+            watch: None,
+            versus: false,
+            autoplay: false,
+            bots: Vec::new(),
+            bot_timeout: external::DEFAULT_TIMEOUT_MS,
+            tournament: false,
+            seeds: None,
+            simulate: false,
+            player: "autopilot".to_string(),
+            tuning: Tuning::default(),
+            invulnerable_ms: player::INVULNERABLE_MS,
+            export_sfx: None,
+            sfx: None,
+            no_sound: false,
+            music: None,
+            no_music: false,
+            glyphs: None,
+            colors: None,
+            sprites: None,
+            theme: None,
+            renderer: Renderer::default(),
+            no_stars: false,
+            no_flash: false,
+            shake: None,
+            hit_stop: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn from_args() -> Result<Self, String> {
+        Self::parse(env::args().skip(1))
+    }
+
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--broadcast" => options.broadcast = Some(value(&arg, args.next())?),
+                "--watch" => options.watch = Some(value(&arg, args.next())?),
+                "--versus" => options.versus = true,
+                "--autoplay" => options.autoplay = true,
+                "--bot" => options.bots.push(value(&arg, args.next())?),
+                "--bot-timeout" => options.bot_timeout = number(&arg, args.next())?,
+                "--tournament" => options.tournament = true,
+                "--seeds" => options.seeds = Some(number(&arg, args.next())?),
+                "simulate" => options.simulate = true,
+                "--player" => options.player = value(&arg, args.next())?,
+                "--export-sfx" => options.export_sfx = Some(value(&arg, args.next())?),
+                "--sfx" => options.sfx = Some(value(&arg, args.next())?),
+                "--no-sound" => options.no_sound = true,
+                "--music" => options.music = Some(value(&arg, args.next())?),
+                "--no-music" => options.no_music = true,
+                "--glyphs" => {
+                    let value = value(&arg, args.next())?;
+                    options.glyphs = Some(
+                        GlyphSet::parse(&value)
+                            .ok_or_else(|| format!("{} needs ascii or unicode", arg))?,
+                    );
+                }
+                "--ascii" => options.glyphs = Some(GlyphSet::Ascii),
+                "--colors" => {
+                    let value = value(&arg, args.next())?;
+                    options.colors = Some(
+                        ColorDepth::parse(&value)
+                            .ok_or_else(|| format!("{} needs truecolor, 256 or 16", arg))?,
+                    );
+                }
+                "--sprites" => options.sprites = Some(value(&arg, args.next())?),
+                "--theme" => options.theme = Some(value(&arg, args.next())?),
+                "--no-stars" => options.no_stars = true,
+                "--no-flash" => options.no_flash = true,
+                "--shake" => options.shake = Some(number(&arg, args.next())?.min(100) as u8),
+                "--hit-stop" => options.hit_stop = Some(number(&arg, args.next())?),
+                "--renderer" => {
+                    let value = value(&arg, args.next())?;
+                    options.renderer = Renderer::parse(&value).ok_or_else(|| {
+                        format!(
+                            "{} needs text, halfblock, braille, kitty, sixel or graphics",
+                            arg
+                        )
+                    })?;
+                }
+                "--march" => {
+                    // start,per level,minimum
+                    let value = value(&arg, args.next())?;
+                    let numbers = numbers(&arg, &value, ',')?;
+                    let [start, level, min] = numbers[..] else {
+                        return Err(format!("{} needs start,level,min", arg));
+                    };
+                    if min < MIN_MARCH_MS {
+                        return Err(format!(
+                            "{} needs a minimum of at least {}",
+                            arg, MIN_MARCH_MS
+                        ));
+                    }
+                    options.tuning.march_ms = start as i64;
+                    options.tuning.march_level_ms = level as i64;
+                    options.tuning.march_min_ms = min as i64;
+                }
+                "--invulnerable" => options.invulnerable_ms = number(&arg, args.next())?,
+                "--fire-window" => {
+                    let value = value(&arg, args.next())?;
+                    let numbers = numbers(&arg, &value, '-')?;
+                    let [min, max] = numbers[..] else {
+                        return Err(format!("{} needs min-max", arg));
+                    };
+                    if min >= max {
+                        return Err(format!("{} needs min below max", arg));
+                    }
+                    options.tuning.fire_min_ms = min;
+                    options.tuning.fire_max_ms = max;
+                }
+                _ => return Err(format!("Unknown argument: {}", arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+fn value(flag: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("{} needs a value", flag))
+}
+
+fn number(flag: &str, value: Option<String>) -> Result<u64, String> {
+    let value = self::value(flag, value)?;
+    value
+        .parse()
+        .map_err(|_| format!("{} needs a number, got {}", flag, value))
+}
+
+fn numbers(flag: &str, value: &str, separator: char) -> Result<Vec<u64>, String> {
+    value
+        .split(separator)
+        .map(|n| number(flag, Some(n.trim().to_string())))
+        .collect()
+}
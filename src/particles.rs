@@ -0,0 +1,293 @@
+use std::{ops::Range, time::Duration};
+
+use crossterm::style::Color;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    events::{Event, Subscriber},
+    frame::{Frame, Updatable},
+    layers::{Layer, Layers},
+    pixels::sprite_color,
+    render::RenderContext,
+    NUM_COLS, NUM_ROWS,
+};
+
+// How long the screen lights up when the cannon is hit
+const FLASH_MS: u64 = 80;
+const FLASH_COLOR: Color = Color::DarkGrey;
+
+// Cells per second squared, for things that fall
+const GRAVITY: f32 = 12.0;
+
+const DEBRIS: Burst = Burst {
+    count: 6,
+    speed: 4.0..10.0,
+    lifetime_ms: 300..600,
+    glyphs: &['#', '%', '+', '.'],
+    gravity: GRAVITY,
+};
+const SPARKS: Burst = Burst {
+    count: 4,
+    speed: 8.0..16.0,
+    lifetime_ms: 100..250,
+    glyphs: &['*', '+', '\'', '.'],
+    gravity: 0.0,
+};
+// smoke drifts upwards
+const SMOKE: Burst = Burst {
+    count: 1,
+    speed: 0.0..1.0,
+    lifetime_ms: 150..300,
+    glyphs: &['O', 'o', '.'],
+    gravity: -2.0,
+};
+
+const SPARK_COLORS: &[Color] = &[
+    Color::White,
+    Color::Yellow,
+    Color::DarkYellow,
+    Color::DarkRed,
+];
+const SMOKE_COLORS: &[Color] = &[Color::Grey, Color::DarkGrey];
+
+/// What a particle is drawn with
+#[derive(Clone, Debug, PartialEq)]
+pub enum Look {
+    /// One character, stepping through these as it ages
+    Glyphs(&'static [char]),
+    /// A line of text centred on the particle, like a score
+    Text(String),
+}
+
+/// Particles thrown out in every direction at once
+#[derive(Clone, Debug)]
+pub struct Burst {
+    pub count: usize,
+    /// Cells per second
+    pub speed: Range<f32>,
+    pub lifetime_ms: Range<u64>,
+    /// Characters it goes through as it ages, first to last
+    pub glyphs: &'static [char],
+    pub gravity: f32,
+}
+
+/// A short lived thing that flies about for show
+#[derive(Clone, Debug)]
+pub struct Particle {
+    /// Position in cells
+    pub x: f32,
+    pub y: f32,
+    /// Cells per second
+    pub velocity: (f32, f32),
+    /// Added to the vertical velocity every second
+    pub gravity: f32,
+    pub lifetime: Duration,
+    pub look: Look,
+    /// Colours it fades through as it ages
+    pub colors: Vec<Color>,
+    age: Duration,
+}
+
+impl Particle {
+    pub fn new(x: f32, y: f32, lifetime: Duration, look: Look, colors: Vec<Color>) -> Self {
+        Self {
+            x,
+            y,
+            velocity: (0.0, 0.0),
+            gravity: 0.0,
+            lifetime,
+            look,
+            colors,
+            age: Duration::ZERO,
+        }
+    }
+
+    pub fn with_velocity(mut self, dx: f32, dy: f32) -> Self {
+        self.velocity = (dx, dy);
+        self
+    }
+
+    pub fn with_gravity(mut self, gravity: f32) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn dead(&self) -> bool {
+        self.age >= self.lifetime
+    }
+
+    // how far through its life it is, from 0 to 1
+    fn progress(&self) -> f32 {
+        (self.age.as_secs_f32() / self.lifetime.as_secs_f32().max(f32::EPSILON)).min(1.0)
+    }
+
+    fn pick<T: Copy>(&self, ramp: &[T]) -> Option<T> {
+        let index = (self.progress() * ramp.len() as f32) as usize;
+        ramp.get(index.min(ramp.len().saturating_sub(1))).copied()
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let color = self.pick(&self.colors);
+        let (x, y) = (self.x.round(), self.y.round());
+        if y < 0.0 || y >= NUM_ROWS as f32 {
+            return;
+        }
+        let y = y as usize;
+
+        match &self.look {
+            Look::Glyphs(glyphs) => {
+                if let Some(c) = self.pick(glyphs) {
+                    if x >= 0.0 && x < NUM_COLS as f32 {
+                        frame.set(x as usize, y, c, color);
+                    }
+                }
+            }
+            Look::Text(text) => {
+                let start = x as i32 - text.chars().count() as i32 / 2;
+                for (i, c) in text.chars().enumerate() {
+                    let x = start + i as i32;
+                    if x >= 0 && x < NUM_COLS as i32 {
+                        frame.set(x as usize, y, c, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Updatable for Particle {
+    fn update(&mut self, delta: Duration) {
+        let seconds = delta.as_secs_f32();
+        self.age += delta;
+        self.x += self.velocity.0 * seconds;
+        self.y += self.velocity.1 * seconds;
+        self.velocity.1 += self.gravity * seconds;
+    }
+}
+
+/// Debris, sparks, smoke and score popups, spawned from game events and
+/// drawn on the effects layer. The game never sees any of it.
+pub struct Particles {
+    particles: Vec<Particle>,
+    flash: Duration,
+    flash_enabled: bool,
+    context: RenderContext,
+    rng: StdRng,
+}
+
+impl Particles {
+    /// The same seed sends the same bits flying the same way. Debris is
+    /// coloured like the sprites in `context`.
+    pub fn new(seed: u64, context: RenderContext) -> Self {
+        Self {
+            particles: Vec::new(),
+            flash: Duration::ZERO,
+            flash_enabled: true,
+            context,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Whether the screen lights up when the cannon is hit
+    pub fn set_flash(&mut self, enabled: bool) {
+        self.flash_enabled = enabled;
+    }
+
+    /// Sends particles flying off from `x`, `y`
+    pub fn burst(&mut self, x: f32, y: f32, burst: &Burst, colors: &[Color]) {
+        for _ in 0..burst.count {
+            let angle = self.rng.gen_range(0.0..std::f32::consts::TAU);
+            let speed = self.rng.gen_range(burst.speed.clone());
+            let lifetime = Duration::from_millis(self.rng.gen_range(burst.lifetime_ms.clone()));
+            let look = Look::Glyphs(burst.glyphs);
+            // cells are about twice as high as they are wide
+            let particle = Particle::new(x, y, lifetime, look, colors.to_vec())
+                .with_velocity(angle.cos() * speed, angle.sin() * speed / 2.0)
+                .with_gravity(burst.gravity);
+            self.particles.push(particle);
+        }
+    }
+
+    /// A number that floats up from where points were scored
+    pub fn popup(&mut self, x: f32, y: f32, text: String, color: Color) {
+        let particle = Particle::new(
+            x,
+            y,
+            Duration::from_millis(800),
+            Look::Text(text),
+            vec![color, color, Color::DarkGrey],
+        )
+        .with_velocity(0.0, -2.0);
+        self.particles.push(particle);
+    }
+
+    pub fn flash(&mut self) {
+        if self.flash_enabled {
+            self.flash = Duration::from_millis(FLASH_MS);
+        }
+    }
+
+    /// Particles go on the effects layer. The flash lights up the
+    /// background instead, on top it would hide the game.
+    pub fn draw_layers(&self, layers: &mut Layers) {
+        let effects = layers.get_mut(Layer::Effects);
+        for particle in &self.particles {
+            particle.draw(effects);
+        }
+
+        if !self.flash.is_zero() {
+            let background = layers.get_mut(Layer::Background);
+            for x in 0..NUM_COLS {
+                for y in 0..NUM_ROWS {
+                    background.set_background(x, y, Some(FLASH_COLOR));
+                }
+            }
+        }
+    }
+}
+
+impl Updatable for Particles {
+    fn update(&mut self, delta: Duration) {
+        for particle in &mut self.particles {
+            particle.update(delta);
+        }
+        self.particles.retain(|particle| !particle.dead());
+        self.flash = self.flash.saturating_sub(delta);
+    }
+}
+
+impl Subscriber for Particles {
+    fn notify(&mut self, event: &Event) {
+        match *event {
+            Event::ShotFired { x, y } => self.burst(x as f32, y as f32 + 1.0, &SMOKE, SMOKE_COLORS),
+            Event::InvaderKilled { kind, x, y, score } => {
+                let color = sprite_color(&self.context, &format!("invader{}", kind), Color::White);
+                let (x, y) = (x as f32, y as f32);
+                self.burst(x, y, &DEBRIS, &[color, Color::DarkGrey]);
+                self.burst(x, y, &SPARKS, SPARK_COLORS);
+                self.popup(x, y, format!("+{}", score), color);
+            }
+            Event::PlayerHit { x, y, .. } => {
+                let color = sprite_color(&self.context, "player", Color::Green);
+                let (x, y) = (x as f32, y as f32);
+                let debris = Burst {
+                    count: 12,
+                    speed: 6.0..16.0,
+                    lifetime_ms: 400..900,
+                    ..DEBRIS
+                };
+                let smoke = Burst {
+                    count: 6,
+                    speed: 1.0..3.0,
+                    lifetime_ms: 800..1400,
+                    ..SMOKE
+                };
+                self.burst(x, y, &debris, &[color, Color::DarkGrey]);
+                self.burst(x, y, &Burst { count: 8, ..SPARKS }, SPARK_COLORS);
+                self.burst(x, y, &smoke, SMOKE_COLORS);
+                self.flash();
+            }
+            _ => {}
+        }
+    }
+}
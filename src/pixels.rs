@@ -0,0 +1,346 @@
+use crossterm::style::Color;
+
+use crate::{
+    braille::{DOTS_X, DOTS_Y},
+    frame::{Drawable, Frame, Transform},
+    game::Game,
+    invader::Invader,
+    layers::{Layer, Layers},
+    player::Player,
+    render::RenderContext,
+    shot::Shot,
+    NUM_COLS, NUM_ROWS,
+};
+
+/// Every cell is two pixels stacked on top of each other
+pub const PIXEL_ROWS: usize = NUM_ROWS * 2;
+
+/// Renderers that show things between cells draw every pixel as a square
+/// of this many dots, braille dots or image pixels
+pub const DOT_SCALE: usize = 2;
+
+// Bitmaps fill the cells the text sprites take up, so invaders are 5x4
+// pixels and the cannon 7x4. `#` is a pixel that's on. The arcade has three
+// kinds of invader over five rows, here every row is its own take on its
+// kind, like the text sprites.
+const INVADER_BITMAPS: [[Bitmap; 2]; 5] = [
+    // the squid
+    [
+        Bitmap(&["..#..", ".###.", "##.##", ".#.#."]),
+        Bitmap(&["..#..", ".###.", "##.##", "#...#"]),
+    ],
+    // the crab
+    [
+        Bitmap(&["#...#", ".###.", "#.#.#", "#...#"]),
+        Bitmap(&[".#.#.", "#####", "#.#.#", ".#.#."]),
+    ],
+    [
+        Bitmap(&["#.#.#", "#####", ".#.#.", "#...#"]),
+        Bitmap(&["#.#.#", "#####", ".#.#.", ".#.#."]),
+    ],
+    // the octopus
+    [
+        Bitmap(&[".###.", "#.#.#", "#####", "#.#.#"]),
+        Bitmap(&[".###.", "#.#.#", "#####", ".#.#."]),
+    ],
+    [
+        Bitmap(&["#####", "#.#.#", "#####", ".#.#."]),
+        Bitmap(&["#####", "#.#.#", "#####", "#...#"]),
+    ],
+];
+const EXPLOSION_BITMAP: Bitmap = Bitmap(&["#.#.#", ".#.#.", "#...#", ".#.#."]);
+const PLAYER_BITMAP: Bitmap = Bitmap(&["...#...", "..###..", ".#####.", "#######"]);
+const PLAYER_EXPLOSION_BITMAPS: [Bitmap; 4] = [
+    Bitmap(&["...#...", ".#.#.#.", "#.###.#", "#######"]),
+    Bitmap(&[".#.#.#.", "#..#..#", ".#.#.#.", "#.###.#"]),
+    Bitmap(&["#..#..#", ".#...#.", "...#...", "#.#.#.#"]),
+    Bitmap(&[".#...#.", ".......", "#..#..#", "......."]),
+];
+const SHOT_BITMAP: Bitmap = Bitmap(&["#", "#"]);
+
+/// A one bit picture, one string per row
+#[derive(Clone, Copy, Debug)]
+pub struct Bitmap(pub &'static [&'static str]);
+
+impl Bitmap {
+    pub fn width(&self) -> usize {
+        self.0.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.0
+            .get(y)
+            .and_then(|row| row.as_bytes().get(x))
+            .is_some_and(|pixel| *pixel == b'#')
+    }
+}
+
+/// Anything that has a bitmap for the half-block renderer
+pub trait Pixels: Transform {
+    fn bitmap(&self) -> Bitmap;
+
+    /// Top left corner in pixels
+    fn pixel_pos(&self) -> (usize, usize) {
+        (self.get_x(), self.get_y() * 2)
+    }
+
+    /// Top left corner in dots, where it's drawn while between cells
+    fn dot_pos(&self) -> (usize, usize) {
+        let (x, y) = self.position();
+        (
+            (x * DOTS_X as f32).round().max(0.0) as usize,
+            (y * DOTS_Y as f32).round().max(0.0) as usize,
+        )
+    }
+
+    /// Whether any pixel that's on overlaps one of `other`'s
+    fn pixel_collision<T: Pixels>(&self, other: &T) -> bool
+    where
+        Self: Sized,
+    {
+        let (bitmap, other_bitmap) = (self.bitmap(), other.bitmap());
+        let (x, y) = self.pixel_pos();
+        let (other_x, other_y) = other.pixel_pos();
+
+        (0..other_bitmap.height()).any(|oy| {
+            (0..other_bitmap.width()).any(|ox| {
+                other_bitmap.get(ox, oy)
+                    && (other_x + ox)
+                        .checked_sub(x)
+                        .zip((other_y + oy).checked_sub(y))
+                        .is_some_and(|(bx, by)| bitmap.get(bx, by))
+            })
+        })
+    }
+
+    /// Like `pixel_collision`, but with both where they're drawn while
+    /// between cells
+    fn dot_collision<T: Pixels>(&self, other: &T) -> bool
+    where
+        Self: Sized,
+    {
+        let (bitmap, other_bitmap) = (self.bitmap(), other.bitmap());
+        let (x, y) = self.dot_pos();
+        let (other_x, other_y) = other.dot_pos();
+
+        (0..other_bitmap.height() * DOT_SCALE).any(|oy| {
+            (0..other_bitmap.width() * DOT_SCALE).any(|ox| {
+                other_bitmap.get(ox / DOT_SCALE, oy / DOT_SCALE)
+                    && (other_x + ox)
+                        .checked_sub(x)
+                        .zip((other_y + oy).checked_sub(y))
+                        .is_some_and(|(bx, by)| bitmap.get(bx / DOT_SCALE, by / DOT_SCALE))
+            })
+        })
+    }
+}
+
+impl Pixels for Invader {
+    fn bitmap(&self) -> Bitmap {
+        if self.is_alive {
+            let frames = &INVADER_BITMAPS[self.kind() % INVADER_BITMAPS.len()];
+            frames[self.frame() % frames.len()]
+        } else {
+            EXPLOSION_BITMAP
+        }
+    }
+}
+
+impl Pixels for Player {
+    fn bitmap(&self) -> Bitmap {
+        if self.dead() {
+            let last = PLAYER_EXPLOSION_BITMAPS.len() - 1;
+            PLAYER_EXPLOSION_BITMAPS[self.explosion_frame().min(last)]
+        } else {
+            PLAYER_BITMAP
+        }
+    }
+}
+
+impl Pixels for Shot {
+    fn bitmap(&self) -> Bitmap {
+        SHOT_BITMAP
+    }
+}
+
+/// How hits are decided
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Collision {
+    /// Overlapping cells, as the text sprites look
+    #[default]
+    Cells,
+    /// Overlapping pixels that are on, as the half-block bitmaps look
+    Pixels,
+    /// Overlapping pixels where they're drawn between cells, as braille and
+    /// images look
+    Dots,
+}
+
+impl Collision {
+    pub fn hits<A: Pixels, B: Pixels>(&self, a: &A, b: &B) -> bool {
+        match self {
+            Collision::Cells => a.check_collision(b),
+            Collision::Pixels => a.pixel_collision(b),
+            Collision::Dots => a.dot_collision(b),
+        }
+    }
+}
+
+/// A picture made of coloured pixels
+#[derive(Clone, Debug, PartialEq)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<Option<Color>>,
+}
+
+impl Canvas {
+    /// `NUM_COLS` pixels wide and `PIXEL_ROWS` high, for half-blocks
+    pub fn new() -> Self {
+        Self::with_size(NUM_COLS, PIXEL_ROWS)
+    }
+
+    pub fn with_size(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![None; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Color> {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x]
+        } else {
+            None
+        }
+    }
+
+    /// Sets a pixel, anything off the canvas is ignored
+    pub fn plot(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = Some(color);
+        }
+    }
+
+    pub fn blit(&mut self, bitmap: Bitmap, x: usize, y: usize, color: Color) {
+        self.blit_scaled(bitmap, x, y, 1, color);
+    }
+
+    /// Draws every bitmap pixel as a `scale` by `scale` square
+    pub fn blit_scaled(&mut self, bitmap: Bitmap, x: usize, y: usize, scale: usize, color: Color) {
+        for by in 0..bitmap.height() * scale {
+            for bx in 0..bitmap.width() * scale {
+                if bitmap.get(bx / scale, by / scale) {
+                    self.plot(x + bx, y + by, color);
+                }
+            }
+        }
+    }
+
+    pub fn draw<T: Pixels + ?Sized>(&mut self, thing: &T, color: Color) {
+        let (x, y) = thing.pixel_pos();
+        self.blit(thing.bitmap(), x, y, color);
+    }
+
+    /// Draws something where it is right now, which can be between cells,
+    /// every pixel `DOT_SCALE` dots across. The canvas starts `top` dots down.
+    pub fn draw_dots<T: Pixels + ?Sized>(&mut self, thing: &T, top: usize, color: Color) {
+        let (x, y) = thing.dot_pos();
+        self.blit_scaled(thing.bitmap(), x, y.saturating_sub(top), DOT_SCALE, color);
+    }
+
+    /// Turns every pair of pixels into a half-block character, the top
+    /// pixel in the foreground colour and the bottom one in the background
+    pub fn to_frame(&self, frame: &mut Frame) {
+        for x in 0..NUM_COLS {
+            for y in 0..NUM_ROWS {
+                let (top, bottom) = (self.get(x, y * 2), self.get(x, y * 2 + 1));
+                let (c, color, background) = match (top, bottom) {
+                    (None, None) => (' ', None, None),
+                    (Some(top), None) => ('▀', Some(top), None),
+                    (None, Some(bottom)) => ('▄', Some(bottom), None),
+                    (Some(top), Some(bottom)) if top == bottom => ('█', Some(top), None),
+                    (Some(top), Some(bottom)) => ('▀', Some(top), Some(bottom)),
+                };
+                frame.set(x, y, c, color);
+                frame.set_background(x, y, background);
+            }
+        }
+    }
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The theme's colour for a sprite, or what the arcade cabinet had
+pub fn sprite_color(context: &RenderContext, name: &str, arcade: Color) -> Color {
+    context
+        .sprites
+        .get(name)
+        .frames
+        .first()
+        .and_then(|frame| frame.color)
+        .or(context.theme.text)
+        .unwrap_or(arcade)
+}
+
+/// The cannon's colour, or its explosion's while it's blowing up
+pub fn player_color(context: &RenderContext, player: &Player) -> Color {
+    if player.dead() {
+        sprite_color(context, "player_explosion", Color::Yellow)
+    } else {
+        sprite_color(context, "player", Color::Green)
+    }
+}
+
+/// Hands everything on the playfield to `plot` with its colour, back to
+/// front. The renderers only differ in where and how big they plot it.
+pub fn paint(game: &Game, context: &RenderContext, mut plot: impl FnMut(&dyn Pixels, Color)) {
+    for invader in &game.army.invaders {
+        let name = if invader.is_alive {
+            format!("invader{}", invader.kind())
+        } else {
+            "explosion".to_string()
+        };
+        plot(invader, sprite_color(context, &name, Color::White));
+    }
+
+    if game.player.visible() {
+        plot(&game.player, player_color(context, &game.player));
+    }
+
+    let up = sprite_color(context, "shot_up", Color::White);
+    for shot in &game.player.shots {
+        plot(shot, up);
+    }
+    let down = sprite_color(context, "shot_down", Color::White);
+    for shot in &game.army.shots {
+        plot(shot, down);
+    }
+}
+
+/// Draws the game as half-block pixels, with the HUD on top as text
+pub fn draw(game: &Game, layers: &mut Layers, context: &RenderContext) {
+    let mut canvas = Canvas::new();
+    paint(game, context, |thing, color| canvas.draw(thing, color));
+
+    canvas.to_frame(layers.get_mut(Layer::Entities));
+    game.army.draw_aim(layers.get_mut(Layer::Entities));
+    game.status.draw(layers.get_mut(Layer::Hud));
+}
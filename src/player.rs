@@ -1,36 +1,57 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use rusty_time::timer::Timer;
 
 const MAX_LIVES: usize = 3;
+const MAX_SHOTS: usize = 3;
+/// How long the cannon can't be hit after coming back, unless it's told otherwise
+pub const INVULNERABLE_MS: u64 = 2000;
+// The cannon blinks this fast while it can't be hit
+const BLINK_MS: u128 = 100;
 
 use crate::{
     frame::{Drawable, Frame, Transform, Updatable},
     shot::{Direction, Shot},
+    sprites::{Sprite, SpriteSheet},
     NUM_COLS, NUM_ROWS,
 };
 
 pub struct Player {
     pub x: usize,
     pub y: usize,
-    pub anim: Vec<&'static str>,
+    pub sprite: Arc<Sprite>,
     pub shots: Vec<Shot>,
+    shot_sprite: Arc<Sprite>,
     anim_idx: usize,
     anim_timer: Timer,
+    explosion: Arc<Sprite>,
+    explosion_idx: usize,
+    explosion_timer: Timer,
+    /// How long it can't be hit after coming back
+    invulnerability: Duration,
+    invulnerable_left: Duration,
 
     pub lives: usize,
     is_alive: bool,
 }
 
 impl Player {
-    pub fn new() -> Self {
+    pub fn new(sheet: &SpriteSheet) -> Self {
+        let sprite = sheet.get("player");
+        let explosion = sheet.get("player_explosion");
         Self {
             x: (NUM_COLS / 2) - 4,
             y: NUM_ROWS - 3,
-            anim: vec![" _/^\\_ \n|#####|"],
             anim_idx: 0,
-            anim_timer: Timer::from_millis(1000),
+            anim_timer: Timer::from_millis(sprite.duration_ms(0)),
+            sprite,
+            explosion_idx: 0,
+            explosion_timer: Timer::from_millis(explosion.duration_ms(0)),
+            explosion,
+            invulnerability: Duration::from_millis(INVULNERABLE_MS),
+            invulnerable_left: Duration::ZERO,
             shots: Vec::new(),
+            shot_sprite: sheet.get("shot_up"),
             lives: MAX_LIVES,
             is_alive: true,
         }
@@ -43,27 +64,77 @@ impl Player {
     }
 
     pub fn move_right(&mut self) {
-        if self.x + 6 < NUM_COLS - 1 {
+        if self.x + self.get_width() < NUM_COLS {
             self.x += 1;
         }
     }
 
     pub fn shoot(&mut self) -> bool {
-        if self.shots.len() < 3 {
-            self.shots
-                .push(Shot::new(self.x + 3, self.y - 1, Direction::Up));
+        if self.shots_left() > 0 {
+            self.shots.push(Shot::new(
+                self.x + self.get_width() / 2,
+                self.y - 1,
+                Direction::Up,
+                self.shot_sprite.clone(),
+            ));
             true
         } else {
             false
         }
     }
 
+    /// How many more shots can be in flight at once
+    pub fn shots_left(&self) -> usize {
+        MAX_SHOTS.saturating_sub(self.shots.len())
+    }
+
+    /// Brings it back, safe from invader shots for a while
     pub fn resurrect(&mut self) {
         self.is_alive = true;
+        self.invulnerable_left = self.invulnerability;
+    }
+
+    /// Zero for not at all
+    pub fn set_invulnerability(&mut self, invulnerability: Duration) {
+        self.invulnerability = invulnerability;
+    }
+
+    pub fn invulnerable(&self) -> bool {
+        self.is_alive && !self.invulnerable_left.is_zero()
+    }
+
+    /// Whether there's anything to draw: it blinks while it can't be hit
+    /// and is gone once it has finished blowing up
+    pub fn visible(&self) -> bool {
+        if self.is_alive {
+            !self.invulnerable()
+                || (self.invulnerable_left.as_millis() / BLINK_MS).is_multiple_of(2)
+        } else {
+            self.exploding()
+        }
+    }
+
+    /// Whether it's been hit and is still blowing up
+    pub fn exploding(&self) -> bool {
+        !self.is_alive && self.explosion_idx < self.explosion.len()
+    }
+
+    /// Which frame of the explosion is showing
+    pub fn explosion_frame(&self) -> usize {
+        self.explosion_idx
+    }
+
+    /// How long blowing up takes
+    pub fn explosion_ms(&self) -> u64 {
+        (0..self.explosion.len())
+            .map(|idx| self.explosion.duration_ms(idx))
+            .sum()
     }
 
     pub fn die(&mut self) -> Option<usize> {
         self.is_alive = false;
+        self.explosion_idx = 0;
+        self.explosion_timer = Timer::from_millis(self.explosion.duration_ms(0));
         self.lives -= 1;
         if self.lives == 0 {
             None
@@ -81,6 +152,12 @@ impl Player {
     }
 }
 
+impl Default for Player {
+    fn default() -> Self {
+        Self::new(&SpriteSheet::builtin())
+    }
+}
+
 impl Transform for Player {
     fn get_x(&self) -> usize {
         self.x
@@ -91,16 +168,38 @@ impl Transform for Player {
     }
 
     fn get_width(&self) -> usize {
-        7
+        self.sprite.width()
     }
 
     fn get_height(&self) -> usize {
-        2
+        self.sprite.height()
+    }
+
+    fn hitbox(&self) -> (usize, usize, usize, usize) {
+        let hitbox = self.sprite.hitbox();
+        (
+            self.x + hitbox.x,
+            self.y + hitbox.y,
+            hitbox.width,
+            hitbox.height,
+        )
     }
 }
 
 impl Updatable for Player {
     fn update(&mut self, delta: Duration) {
+        // a dead cannon only blows up
+        if !self.is_alive {
+            self.explosion_timer.update(delta);
+            if self.explosion_timer.ready && self.explosion_idx < self.explosion.len() {
+                self.explosion_idx += 1;
+                let duration = self.explosion.duration_ms(self.explosion_idx);
+                self.explosion_timer = Timer::from_millis(duration);
+            }
+            return;
+        }
+        self.invulnerable_left = self.invulnerable_left.saturating_sub(delta);
+
         // remove dead shots
         self.shots.retain(|shot| !shot.dead());
 
@@ -110,20 +209,20 @@ impl Updatable for Player {
 
         self.anim_timer.update(delta);
         if self.anim_timer.ready {
-            self.anim_idx = (self.anim_idx + 1) % self.anim.len();
-            self.anim_timer.reset();
+            self.anim_idx = (self.anim_idx + 1) % self.sprite.len();
+            self.anim_timer = Timer::from_millis(self.sprite.duration_ms(self.anim_idx));
         }
     }
 }
 
 impl Drawable for Player {
     fn draw(&self, frame: &mut Frame) {
-        if self.is_alive {
-            let lines = self.anim[self.anim_idx].lines();
-            for (y, line) in lines.enumerate() {
-                for (x, c) in line.chars().enumerate() {
-                    frame[self.x + x][self.y + y] = c;
-                }
+        if self.visible() {
+            if self.is_alive {
+                self.sprite.draw(frame, self.x, self.y, self.anim_idx);
+            } else {
+                self.explosion
+                    .draw(frame, self.x, self.y, self.explosion_idx);
             }
         }
 
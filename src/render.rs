@@ -1,30 +1,171 @@
-use std::io::{Stdout, Write};
+use std::{io::Write, sync::Arc};
 
 use crossterm::{style::Color, terminal::*, *};
 
-use crate::frame::Frame;
+use crate::{
+    braille,
+    colors::ColorDepth,
+    frame::Frame,
+    game::Game,
+    glyphs::GlyphSet,
+    graphics::{self, Protocol},
+    layers::Layers,
+    pixels::{self, Canvas, Collision},
+    sprites::SpriteSheet,
+    theme::Theme,
+};
 
-pub fn render(stdout: &mut Stdout, last_frame: &Frame, curr_frame: &Frame, force: bool) {
+/// How the game is turned into a frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Renderer {
+    /// A character per cell, drawn with the sprites
+    #[default]
+    Text,
+    /// Two pixels per cell with half-block characters, drawn with bitmaps
+    HalfBlock,
+    /// Eight dots per cell with braille characters, things move smoothly
+    /// between cells
+    Braille,
+    /// An image drawn by the terminal, with the text frame for the HUD.
+    /// The starfield, particles, flash and shake are only in the text
+    /// frame, the image doesn't have them.
+    Graphics(Protocol),
+}
+
+impl Renderer {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Renderer::Text),
+            "halfblock" => Some(Renderer::HalfBlock),
+            "braille" => Some(Renderer::Braille),
+            "kitty" => Some(Renderer::Graphics(Protocol::Kitty)),
+            "sixel" => Some(Renderer::Graphics(Protocol::Sixel)),
+            // whatever the terminal can show, text if it can't show images
+            "graphics" => Some(Protocol::detect().map_or(Renderer::Text, Renderer::Graphics)),
+            _ => None,
+        }
+    }
+
+    /// Whether it draws with characters an ASCII terminal doesn't have
+    pub fn needs_unicode(&self) -> bool {
+        matches!(self, Renderer::HalfBlock | Renderer::Braille)
+    }
+
+    /// Images still get the text version, for spectators and the HUD
+    pub fn draw(&self, game: &Game, layers: &mut Layers, context: &RenderContext) {
+        match self {
+            Renderer::Text | Renderer::Graphics(_) => game.draw_layers(layers),
+            Renderer::HalfBlock => pixels::draw(game, layers, context),
+            Renderer::Braille => braille::draw(game, layers, context),
+        }
+    }
+
+    /// Collisions that match what this renderer shows
+    pub fn collision(&self) -> Collision {
+        match self {
+            Renderer::Text => Collision::Cells,
+            Renderer::HalfBlock => Collision::Pixels,
+            Renderer::Braille | Renderer::Graphics(_) => Collision::Dots,
+        }
+    }
+
+    /// The picture to send along with the frame, for renderers that draw images
+    pub fn image(&self, game: &Game, context: &RenderContext) -> Option<Canvas> {
+        match self {
+            Renderer::Graphics(_) => Some(graphics::canvas(game, context)),
+            _ => None,
+        }
+    }
+}
+
+/// How to show frames on this terminal. Handed to whatever renders, so
+/// two renderers in one process can look different.
+#[derive(Clone)]
+pub struct RenderContext {
+    pub glyphs: GlyphSet,
+    /// The same sheet the game was made with, for the colours
+    pub sprites: Arc<SpriteSheet>,
+    pub theme: Arc<Theme>,
+    /// The theme is in truecolour, the terminal may not be
+    pub colors: ColorDepth,
+}
+
+impl Default for RenderContext {
+    fn default() -> Self {
+        Self {
+            glyphs: GlyphSet::Unicode,
+            sprites: Arc::new(SpriteSheet::builtin()),
+            theme: Arc::new(Theme::default()),
+            colors: ColorDepth::TrueColor,
+        }
+    }
+}
+
+pub fn render<W: Write>(
+    stdout: &mut W,
+    last_frame: &Frame,
+    curr_frame: &Frame,
+    force: bool,
+    context: &RenderContext,
+) {
+    let theme = &context.theme;
+    let depth = context.colors;
     if force {
         // clear the screen
         stdout
-            .queue(style::SetBackgroundColor(Color::Blue))
+            .queue(style::SetBackgroundColor(depth.convert(theme.border)))
             .unwrap();
         stdout.queue(terminal::Clear(ClearType::All)).unwrap();
 
         stdout
-            .queue(style::SetBackgroundColor(Color::Black))
+            .queue(style::SetBackgroundColor(depth.convert(theme.background)))
             .unwrap();
     }
 
+    // the colours the terminal is currently set to
+    let mut color = None;
+    let mut background = theme.background;
+
     for (x, col) in curr_frame.iter().enumerate() {
         for (y, s) in col.iter().enumerate() {
-            if last_frame[x][y] != *s || force {
+            let cell_color = curr_frame.color(x, y).or(theme.text);
+            let cell_background = curr_frame.background(x, y).unwrap_or(theme.background);
+            let changed = last_frame[x][y] != *s
+                || last_frame.color(x, y).or(theme.text) != cell_color
+                || last_frame.background(x, y).unwrap_or(theme.background) != cell_background;
+
+            if changed || force {
+                if cell_color != color {
+                    let fg = cell_color.unwrap_or(Color::Reset);
+                    stdout
+                        .queue(style::SetForegroundColor(depth.convert(fg)))
+                        .unwrap();
+                    color = cell_color;
+                }
+                if cell_background != background {
+                    stdout
+                        .queue(style::SetBackgroundColor(depth.convert(cell_background)))
+                        .unwrap();
+                    background = cell_background;
+                }
                 stdout.queue(cursor::MoveTo(x as u16, y as u16)).unwrap();
-                stdout.queue(style::Print(*s)).unwrap();
+                stdout
+                    .queue(style::Print(context.glyphs.convert(*s)))
+                    .unwrap();
             }
         }
     }
 
+    if color.is_some() {
+        stdout
+            .queue(style::SetForegroundColor(Color::Reset))
+            .unwrap();
+    }
+    if background != theme.background {
+        stdout
+            .queue(style::SetBackgroundColor(depth.convert(theme.background)))
+            .unwrap();
+    }
+
     stdout.flush().unwrap();
 }
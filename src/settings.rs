@@ -0,0 +1,156 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::sound::{Mixer, DEFAULT_VOICES};
+
+/// Preferences that survive between runs. Stored as `key = value` lines,
+/// unknown keys are ignored so older versions can read newer files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// Volumes in percent
+    pub master_volume: u8,
+    pub effects_volume: u8,
+    pub music_volume: u8,
+    pub muted: bool,
+    /// How many sounds may play at once
+    pub voices: usize,
+    /// A built-in theme, a theme directory or the name of one in `themes`
+    pub theme: String,
+    /// The starfield behind the game, some find it distracting
+    pub stars: bool,
+    /// How hard the playfield shakes when the cannon is hit, in percent
+    pub shake: u8,
+    /// How long the game freezes when the cannon is hit
+    pub hit_stop_ms: u64,
+    /// The screen lighting up when the cannon is hit, hard on some eyes
+    pub flash: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            master_volume: 100,
+            effects_volume: 100,
+            music_volume: 100,
+            muted: false,
+            voices: DEFAULT_VOICES,
+            theme: "classic".to_string(),
+            stars: true,
+            shake: 100,
+            hit_stop_ms: 100,
+            flash: true,
+        }
+    }
+}
+
+impl Settings {
+    /// `$INVADERS_SETTINGS`, otherwise `invaders/settings.conf` in the user's config directory
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("INVADERS_SETTINGS") {
+            return Some(PathBuf::from(path));
+        }
+
+        let config = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+
+        Some(config.join("invaders").join("settings.conf"))
+    }
+
+    /// Reads the settings file, anything missing or unreadable keeps its default
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| Self::load_from(path).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn load_from<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut settings = Self::default();
+
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim() {
+                "master_volume" => set(&mut settings.master_volume, value),
+                "effects_volume" => set(&mut settings.effects_volume, value),
+                "music_volume" => set(&mut settings.music_volume, value),
+                "muted" => set(&mut settings.muted, value),
+                "voices" => set(&mut settings.voices, value),
+                "theme" => settings.theme = value.to_string(),
+                "stars" => set(&mut settings.stars, value),
+                "shake" => set(&mut settings.shake, value),
+                "hit_stop_ms" => set(&mut settings.hit_stop_ms, value),
+                "flash" => set(&mut settings.flash, value),
+                _ => {}
+            }
+        }
+
+        settings.master_volume = settings.master_volume.min(100);
+        settings.effects_volume = settings.effects_volume.min(100);
+        settings.music_volume = settings.music_volume.min(100);
+        settings.shake = settings.shake.min(100);
+
+        Ok(settings)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No place to store settings"))?;
+        self.save_to(path)
+    }
+
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let contents = format!(
+            "master_volume = {}\neffects_volume = {}\nmusic_volume = {}\nmuted = {}\nvoices = {}\ntheme = {}\nstars = {}\nshake = {}\nhit_stop_ms = {}\nflash = {}\n",
+            self.master_volume,
+            self.effects_volume,
+            self.music_volume,
+            self.muted,
+            self.voices,
+            self.theme,
+            self.stars,
+            self.shake,
+            self.hit_stop_ms,
+            self.flash,
+        );
+        fs::write(path, contents)
+    }
+
+    pub fn mixer(&self) -> Mixer {
+        Mixer {
+            master: self.master_volume as f32 / 100.0,
+            effects: self.effects_volume as f32 / 100.0,
+            music: self.music_volume as f32 / 100.0,
+            muted: self.muted,
+        }
+    }
+
+    /// Takes over volume changes made while playing
+    pub fn update_from(&mut self, mixer: &Mixer) {
+        self.master_volume = (mixer.master * 100.0).round() as u8;
+        self.effects_volume = (mixer.effects * 100.0).round() as u8;
+        self.music_volume = (mixer.music * 100.0).round() as u8;
+        self.muted = mixer.muted;
+    }
+}
+
+// keeps the current value when the file has garbage in it
+fn set<T: std::str::FromStr>(field: &mut T, value: &str) {
+    if let Ok(value) = value.parse() {
+        *field = value;
+    }
+}
@@ -1,9 +1,10 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use rusty_time::timer::Timer;
 
 use crate::{
-    frame::{Drawable, Frame, Transform, Updatable},
+    frame::{self, Drawable, Frame, Transform, Updatable},
+    sprites::Sprite,
     NUM_ROWS,
 };
 
@@ -18,16 +19,19 @@ pub struct Shot {
     timer: Timer,
     direction: Direction,
     exploding: bool,
+    sprite: Arc<Sprite>,
 }
 
 impl Shot {
-    pub fn new(x: usize, y: usize, direction: Direction) -> Self {
+    /// `sprite` is `shot_up` or `shot_down`, whichever way it's going
+    pub fn new(x: usize, y: usize, direction: Direction, sprite: Arc<Sprite>) -> Self {
         Self {
             x,
             y,
             timer: Timer::from_millis(75),
             direction,
             exploding: false,
+            sprite,
         }
     }
 
@@ -69,6 +73,17 @@ impl Transform for Shot {
     fn get_height(&self) -> usize {
         1
     }
+
+    fn position(&self) -> (f32, f32) {
+        if self.dead() {
+            return (self.x as f32, self.y as f32);
+        }
+        let step = frame::progress(&self.timer);
+        match self.direction {
+            Direction::Up => (self.x as f32, self.y as f32 - step),
+            Direction::Down => (self.x as f32, self.y as f32 + step),
+        }
+    }
 }
 
 impl Updatable for Shot {
@@ -86,9 +101,6 @@ impl Updatable for Shot {
 
 impl Drawable for Shot {
     fn draw(&self, frame: &mut Frame) {
-        frame[self.x][self.y] = match self.direction {
-            Direction::Up => '↑',
-            Direction::Down => '↓',
-        };
+        self.sprite.draw(frame, self.x, self.y, 0);
     }
 }
@@ -0,0 +1,239 @@
+use std::{fmt, sync::Arc, thread, time::Duration};
+
+use crate::{
+    army::Tuning,
+    bot::{self, Bot},
+    events::{Event, Subscriber},
+    game::{Game, GameOver},
+    sprites::SpriteSheet,
+};
+
+/// Simulated games are called a draw after this much game time
+pub const MAX_GAME_SECS: u64 = 60 * 60;
+
+/// Everything measured during one simulated game
+pub struct GameStats {
+    pub seed: u64,
+    pub level: u64,
+    pub score: usize,
+    /// Game time it took to clear each wave, in order
+    pub wave_times: Vec<Duration>,
+    pub game_over: Option<GameOver>,
+    /// Lives lost to invader shots, including the last one
+    pub shot_deaths: usize,
+    pub shots_fired: usize,
+    pub hits: usize,
+    // game time spent in the current wave so far
+    wave_time: Duration,
+}
+
+impl Subscriber for GameStats {
+    fn notify(&mut self, event: &Event) {
+        match event {
+            Event::ShotFired { .. } => self.shots_fired += 1,
+            Event::InvaderKilled { .. } => self.hits += 1,
+            Event::PlayerHit { .. } => self.shot_deaths += 1,
+            Event::LevelCleared { .. } => {
+                self.wave_times.push(self.wave_time);
+                self.wave_time = Duration::ZERO;
+            }
+            Event::GameOver(reason) => self.game_over = Some(*reason),
+            _ => {}
+        }
+    }
+}
+
+/// Plays one game to the end without a terminal, `bot` acts every `bot::THINK_MS`
+pub fn play(
+    seed: u64,
+    tuning: Tuning,
+    invulnerability: Duration,
+    sprites: Arc<SpriteSheet>,
+    bot: &mut dyn Bot,
+) -> GameStats {
+    let mut game = Game::with_sprites(seed, tuning, sprites);
+    game.player.set_invulnerability(invulnerability);
+    let step = Duration::from_millis(bot::THINK_MS);
+
+    let mut stats = GameStats {
+        seed,
+        level: 0,
+        score: 0,
+        wave_times: Vec::new(),
+        game_over: None,
+        shot_deaths: 0,
+        shots_fired: 0,
+        hits: 0,
+        wave_time: Duration::ZERO,
+    };
+
+    let mut elapsed = Duration::ZERO;
+
+    while elapsed < Duration::from_secs(MAX_GAME_SECS) && game.game_over().is_none() {
+        if !game.player.dead() {
+            let action = bot.act(&game.player, &game.army);
+            game.act(action);
+        }
+
+        game.update(step);
+        elapsed += step;
+        stats.wave_time += step;
+
+        game.events.publish(&mut [&mut stats]);
+    }
+
+    stats.level = game.status.level;
+    stats.score = game.status.score;
+    stats
+}
+
+/// Plays `games` seeded games spread over all cores. `make_bot` is called
+/// once per game so bots start each game fresh.
+pub fn simulate<F>(
+    games: u64,
+    tuning: Tuning,
+    invulnerability: Duration,
+    sprites: Arc<SpriteSheet>,
+    make_bot: F,
+) -> Report
+where
+    F: Fn() -> Box<dyn Bot> + Sync,
+{
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()) as u64;
+
+    let mut games: Vec<GameStats> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|worker| {
+                let make_bot = &make_bot;
+                let sprites = &sprites;
+                scope.spawn(move || {
+                    (worker..games)
+                        .step_by(workers as usize)
+                        .map(|seed| {
+                            play(
+                                seed,
+                                tuning,
+                                invulnerability,
+                                sprites.clone(),
+                                make_bot().as_mut(),
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    games.sort_by_key(|game| game.seed);
+    Report {
+        tuning,
+        invulnerability,
+        games,
+    }
+}
+
+pub struct Report {
+    pub tuning: Tuning,
+    pub invulnerability: Duration,
+    pub games: Vec<GameStats>,
+}
+
+// "mean  min  p10  median  p90  max" of a set of values
+fn distribution(mut values: Vec<f64>) -> String {
+    if values.is_empty() {
+        return "-".to_string();
+    }
+
+    values.sort_by(|a, b| a.total_cmp(b));
+    let percentile = |p: usize| values[(values.len() - 1) * p / 100];
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+
+    format!(
+        "{:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+        mean,
+        percentile(0),
+        percentile(10),
+        percentile(50),
+        percentile(90),
+        percentile(100)
+    )
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let games = &self.games;
+        let t = &self.tuning;
+
+        writeln!(f, "Games: {}", games.len())?;
+        writeln!(
+            f,
+            "Tuning: march {}ms -{}ms/level (min {}ms), fire window {}..{}ms, invulnerable {}ms",
+            t.march_ms,
+            t.march_level_ms,
+            t.march_min_ms,
+            t.fire_min_ms,
+            t.fire_max_ms,
+            self.invulnerability.as_millis()
+        )?;
+        writeln!(f)?;
+
+        writeln!(
+            f,
+            "{:<18}{:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            "", "mean", "min", "p10", "median", "p90", "max"
+        )?;
+        let rows: [(&str, Vec<f64>); 3] = [
+            (
+                "Level reached",
+                games.iter().map(|g| g.level as f64).collect(),
+            ),
+            ("Score", games.iter().map(|g| g.score as f64).collect()),
+            (
+                "Lives lost (shot)",
+                games.iter().map(|g| g.shot_deaths as f64).collect(),
+            ),
+        ];
+        for (name, values) in rows {
+            writeln!(f, "{:<18}{}", name, distribution(values))?;
+        }
+        writeln!(f)?;
+
+        let waves = games.iter().map(|g| g.wave_times.len()).max().unwrap_or(0);
+        writeln!(f, "Seconds to clear wave:")?;
+        for wave in 0..waves {
+            let times: Vec<f64> = games
+                .iter()
+                .filter_map(|g| g.wave_times.get(wave))
+                .map(|t| t.as_secs_f64())
+                .collect();
+            let label = format!("Wave {} (n={})", wave + 1, times.len());
+            writeln!(f, "{:<18}{}", label, distribution(times))?;
+        }
+        writeln!(f)?;
+
+        let count = |cause| games.iter().filter(|g| g.game_over == cause).count();
+        writeln!(f, "Game over by:")?;
+        writeln!(f, "  shot            {}", count(Some(GameOver::Shot)))?;
+        writeln!(f, "  invaded         {}", count(Some(GameOver::Invaded)))?;
+        writeln!(f, "  time limit      {}", count(None))?;
+        writeln!(f)?;
+
+        let fired: usize = games.iter().map(|g| g.shots_fired).sum();
+        let hits: usize = games.iter().map(|g| g.hits).sum();
+        let accuracy = if fired > 0 {
+            hits as f64 * 100.0 / fired as f64
+        } else {
+            0.0
+        };
+        writeln!(
+            f,
+            "Shot accuracy: {:.1}% ({} hits / {} shots)",
+            accuracy, hits, fired
+        )
+    }
+}
@@ -1,56 +1,663 @@
 use std::{
     collections::HashMap,
-    path::{Path, PathBuf},
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek},
+    path::Path,
+    time::{Duration, Instant},
 };
 
-use rusty_audio::Audio;
+use rand::Rng;
+use rodio::{
+    buffer::SamplesBuffer, source::ChannelVolume, Decoder, OutputStream, OutputStreamHandle, Sink,
+    Source,
+};
+
+use crate::{
+    assets,
+    events::{Event, Subscriber},
+    music::{Music, Scene},
+    synth, NUM_COLS,
+};
+
+/// Number of sounds that can play at the same time unless configured otherwise
+pub const DEFAULT_VOICES: usize = 4;
+
+/// The same sound won't be started again within this time, so a burst of
+/// explosions doesn't turn into noise
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_millis(60);
+
+/// Every play is pitched up or down by a random amount up to this fraction,
+/// so repeated sounds don't come out identical
+pub const DEFAULT_PITCH_VARIATION: f32 = 0.05;
+
+/// Which volume slider a clip answers to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Effects,
+    Music,
+}
+
+/// When all voices are busy, a sound cuts off one with a lower priority
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// How to play a single sound
+#[derive(Clone, Copy, Debug)]
+pub struct Playback {
+    pub priority: Priority,
+    /// Multiplied with the mixer volumes, 1.0 is unchanged
+    pub volume: f32,
+    /// -1.0 is hard left, 1.0 hard right
+    pub pan: f32,
+    /// Playback speed, 2.0 is an octave up
+    pub pitch: f32,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            priority: Priority::Normal,
+            volume: 1.0,
+            pan: 0.0,
+            pitch: 1.0,
+        }
+    }
+}
+
+impl Playback {
+    /// Panned to where column `x` is on screen
+    pub fn at(x: usize) -> Self {
+        Self {
+            pan: (x.min(NUM_COLS - 1) as f32 / (NUM_COLS - 1) as f32) * 2.0 - 1.0,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_priority(self, priority: Priority) -> Self {
+        Self { priority, ..self }
+    }
+
+    /// Left and right channel volume. The centre plays at full volume on
+    /// both sides, moving away from it fades out the other side.
+    pub fn stereo_gains(&self) -> (f32, f32) {
+        let pan = self.pan.clamp(-1.0, 1.0);
+        ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0))
+    }
+}
+
+/// Volume levels from 0.0 to 1.0
+#[derive(Clone, Copy, Debug)]
+pub struct Mixer {
+    pub master: f32,
+    pub effects: f32,
+    pub music: f32,
+    pub muted: bool,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            effects: 1.0,
+            music: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl Mixer {
+    pub fn gain(&self, category: Category) -> f32 {
+        if self.muted {
+            return 0.0;
+        }
+        let category = match category {
+            Category::Effects => self.effects,
+            Category::Music => self.music,
+        };
+        self.master * category
+    }
+}
+
+/// A decoded sound, ready to be played any number of times
+pub struct Clip {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+    pub category: Category,
+    pub cooldown: Duration,
+}
+
+/// Something that can make clips audible, or pretend to
+pub trait Backend {
+    fn name(&self) -> &'static str;
+    /// `playback.volume` already has the mixer applied
+    fn play(&mut self, tick: u64, name: &str, clip: &Clip, playback: &Playback);
+    /// Whether anything is still playing
+    fn busy(&self) -> bool;
+    /// Sounds that were played, if this backend keeps track
+    fn played(&self) -> &[(u64, String)] {
+        &[]
+    }
+    /// Replaces the background music, which doesn't take up a voice
+    fn play_music(&mut self, tick: u64, name: &str, source: MusicSource, volume: f32);
+    fn stop_music(&mut self);
+    fn set_music_volume(&mut self, volume: f32);
+}
+
+/// A song rendered on the fly, see `music::SongPlayer`
+pub type MusicSource = Box<dyn Source<Item = i16> + Send>;
+
+struct Voice {
+    sink: Sink,
+    priority: Priority,
+}
+
+/// Plays through the default audio device
+pub struct DeviceBackend {
+    // kept alive for as long as we want to hear anything
+    output: (OutputStream, OutputStreamHandle),
+    voices: Vec<Voice>,
+    music: Option<Sink>,
+}
+
+impl DeviceBackend {
+    pub fn try_new(voices: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        let output = OutputStream::try_default()?;
+        let voices = (0..voices.max(1))
+            .map(|_| {
+                Ok(Voice {
+                    sink: Sink::try_new(&output.1)?,
+                    priority: Priority::Low,
+                })
+            })
+            .collect::<Result<_, rodio::PlayError>>()?;
+
+        Ok(Self {
+            output,
+            voices,
+            music: None,
+        })
+    }
+
+    // a free voice, or the least important one if it matters less than `priority`
+    fn pick_voice(&self, priority: Priority) -> Option<usize> {
+        if let Some(free) = self.voices.iter().position(|voice| voice.sink.empty()) {
+            return Some(free);
+        }
+
+        self.voices
+            .iter()
+            .enumerate()
+            .filter(|(_, voice)| voice.priority < priority)
+            .min_by_key(|(_, voice)| voice.priority)
+            .map(|(i, _)| i)
+    }
+}
+
+impl Backend for DeviceBackend {
+    fn name(&self) -> &'static str {
+        "device"
+    }
+
+    fn play(&mut self, _tick: u64, _name: &str, clip: &Clip, playback: &Playback) {
+        let Some(index) = self.pick_voice(playback.priority) else {
+            return;
+        };
+
+        let voice = &mut self.voices[index];
+        if !voice.sink.empty() {
+            // dropping a sink cuts off whatever it was playing
+            match Sink::try_new(&self.output.1) {
+                Ok(sink) => voice.sink = sink,
+                Err(_) => return,
+            }
+        }
+
+        let source = SamplesBuffer::new(clip.channels, clip.sample_rate, clip.samples.clone())
+            .speed(playback.pitch)
+            .amplify(playback.volume);
+
+        if playback.pan == 0.0 {
+            // leave stereo clips alone when there is nothing to pan
+            voice.sink.append(source);
+        } else {
+            let (left, right) = playback.stereo_gains();
+            voice
+                .sink
+                .append(ChannelVolume::new(source, vec![left, right]));
+        }
+        voice.priority = playback.priority;
+    }
+
+    fn busy(&self) -> bool {
+        let music = self.music.as_ref().is_some_and(|sink| !sink.empty());
+        music || self.voices.iter().any(|voice| !voice.sink.empty())
+    }
+
+    fn play_music(&mut self, _tick: u64, _name: &str, source: MusicSource, volume: f32) {
+        // a new sink rather than appending, the old song stops right away
+        self.music = Sink::try_new(&self.output.1).ok();
+        if let Some(sink) = &self.music {
+            sink.set_volume(volume);
+            sink.append(source);
+        }
+    }
+
+    fn stop_music(&mut self) {
+        self.music = None;
+    }
+
+    fn set_music_volume(&mut self, volume: f32) {
+        if let Some(sink) = &self.music {
+            sink.set_volume(volume);
+        }
+    }
+}
+
+/// Plays nothing. Used when there is no audio device and with `--no-sound`,
+/// a recording one also remembers what would have been played and on which
+/// tick, for tests.
+#[derive(Default)]
+pub struct NullBackend {
+    played: Option<Vec<(u64, String)>>,
+}
+
+impl NullBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps every sound it is asked to play, see `Backend::played`
+    pub fn recording() -> Self {
+        Self {
+            played: Some(Vec::new()),
+        }
+    }
+
+    fn record(&mut self, tick: u64, name: String) {
+        if let Some(played) = &mut self.played {
+            played.push((tick, name));
+        }
+    }
+}
+
+impl Backend for NullBackend {
+    fn name(&self) -> &'static str {
+        "null"
+    }
+
+    fn play(&mut self, tick: u64, name: &str, _clip: &Clip, _playback: &Playback) {
+        self.record(tick, name.to_string());
+    }
+
+    fn busy(&self) -> bool {
+        false
+    }
+
+    fn played(&self) -> &[(u64, String)] {
+        self.played.as_deref().unwrap_or(&[])
+    }
+
+    fn play_music(&mut self, tick: u64, name: &str, _source: MusicSource, _volume: f32) {
+        self.record(tick, format!("music:{}", name));
+    }
+
+    fn stop_music(&mut self) {}
+
+    fn set_music_volume(&mut self, _volume: f32) {}
+}
 
 pub struct Sound {
-    audio: Audio,
-    files: HashMap<String, PathBuf>,
+    backend: Box<dyn Backend>,
+    clips: HashMap<String, Clip>,
+    tick: u64,
+    mixer: Mixer,
+    pitch_variation: f32,
+    // when each sound last started, and how important it was
+    last_played: HashMap<String, (Instant, Priority)>,
 }
 
 impl Sound {
+    /// Uses the audio device if there is one and falls back to silence
     pub fn new() -> Self {
+        Self::with_voices(DEFAULT_VOICES)
+    }
+
+    /// Like `new`, with at most `voices` sounds playing at once
+    pub fn with_voices(voices: usize) -> Self {
+        match DeviceBackend::try_new(voices) {
+            Ok(backend) => Self::with_backend(Box::new(backend)),
+            Err(_) => Self::silent(),
+        }
+    }
+
+    pub fn silent() -> Self {
+        Self::with_backend(Box::new(NullBackend::new()))
+    }
+
+    pub fn with_backend(backend: Box<dyn Backend>) -> Self {
         Self {
-            audio: Audio::new(),
-            files: HashMap::new(),
+            backend,
+            clips: HashMap::new(),
+            tick: 0,
+            mixer: Mixer::default(),
+            pitch_variation: DEFAULT_PITCH_VARIATION,
+            last_played: HashMap::new(),
         }
     }
 
+    pub fn mixer(&self) -> &Mixer {
+        &self.mixer
+    }
+
+    /// Changes the volumes, including the music that is already playing
+    pub fn set_mixer(&mut self, mixer: Mixer) {
+        self.mixer = mixer;
+        self.backend
+            .set_music_volume(self.mixer.gain(Category::Music));
+    }
+
+    /// How far each play may be randomly detuned, 0.0 turns it off
+    pub fn set_pitch_variation(&mut self, variation: f32) {
+        self.pitch_variation = variation.clamp(0.0, 0.5);
+    }
+
+    /// Moves a registered sound to another volume slider
+    pub fn set_category(&mut self, name: &str, category: Category) {
+        if let Some(clip) = self.clips.get_mut(name) {
+            clip.category = category;
+        }
+    }
+
+    /// Changes how soon a registered sound may be started again
+    pub fn set_cooldown(&mut self, name: &str, cooldown: Duration) {
+        if let Some(clip) = self.clips.get_mut(name) {
+            clip.cooldown = cooldown;
+        }
+    }
+
+    pub fn backend(&self) -> &dyn Backend {
+        self.backend.as_ref()
+    }
+
+    /// Advances the tick sounds are logged against, once per game loop
+    pub fn next_tick(&mut self) {
+        self.tick += 1;
+    }
+
+    /// Registers the default effects embedded in the binary, see `assets::SFX`
+    pub fn add_defaults(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        for (name, bytes) in assets::SFX {
+            self.add_bytes(name, bytes)?;
+        }
+        Ok(())
+    }
+
     pub fn add<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let path = path.as_ref();
         let filename = path
-            .as_ref()
             .file_stem()
-            .unwrap()
-            .to_str()
-            .unwrap()
+            .ok_or("Sound file has no name")?
+            .to_string_lossy()
             .to_string();
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
 
-        self.audio.add(&filename, path.as_ref());
-        self.files.insert(filename, path.as_ref().to_path_buf());
+        // only the decoder the extension asks for gets to look at the file,
+        // some of them choke on broken files meant for another one
+        let reader = BufReader::new(File::open(path)?);
+        let decoder = match extension.as_deref() {
+            Some("wav") => Decoder::new_wav(reader)?,
+            Some("ogg") => Decoder::new_vorbis(reader)?,
+            Some("flac") => Decoder::new_flac(reader)?,
+            Some("mp3") => Decoder::new_mp3(reader)?,
+            _ => Decoder::new(reader)?,
+        };
+        self.add_decoder(&filename, decoder);
+        Ok(())
+    }
 
+    /// Registers an encoded sound (WAV, Vorbis, FLAC or MP3) held in memory
+    pub fn add_bytes<B>(&mut self, name: &str, bytes: B) -> Result<(), Box<dyn std::error::Error>>
+    where
+        B: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        self.add_decoder(name, Decoder::new(Cursor::new(bytes))?);
         Ok(())
     }
 
+    fn add_decoder<R>(&mut self, name: &str, decoder: Decoder<R>)
+    where
+        R: Read + Seek + Send + Sync + 'static,
+    {
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+        self.add_samples(name, channels, sample_rate, decoder.collect());
+    }
+
+    pub fn add_samples(&mut self, name: &str, channels: u16, sample_rate: u32, samples: Vec<i16>) {
+        self.clips.insert(
+            name.to_string(),
+            Clip {
+                channels,
+                sample_rate,
+                samples,
+                category: Category::Effects,
+                cooldown: DEFAULT_COOLDOWN,
+            },
+        );
+    }
+
     pub fn play(&mut self, name: &str) {
-        match self.play_safe(name) {
-            Ok(_) => (),
-            Err(_) => {}
-        }
+        let _ = self.play_safe(name);
+    }
+
+    pub fn play_with(&mut self, name: &str, playback: Playback) {
+        let _ = self.play_safe_with(name, playback);
     }
 
     pub fn play_safe(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.play_safe_with(name, Playback::default())
+    }
+
+    pub fn play_safe_with(
+        &mut self,
+        name: &str,
+        mut playback: Playback,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         // check if we exist in the hashmap
-        if !self.files.contains_key(name) {
-            return Err("Sound not found".into());
+        let clip = self.clips.get(name).ok_or("Sound not found")?;
+
+        let gain = self.mixer.gain(clip.category);
+        if gain <= 0.0 {
+            return Ok(());
+        }
+
+        // only something more important gets through a cooldown
+        let now = Instant::now();
+        if let Some((at, priority)) = self.last_played.get(name) {
+            if now.duration_since(*at) < clip.cooldown && playback.priority <= *priority {
+                return Ok(());
+            }
         }
+        self.last_played
+            .insert(name.to_string(), (now, playback.priority));
 
-        self.audio.play(name);
+        playback.volume *= gain;
+        if self.pitch_variation > 0.0 {
+            let variation = self.pitch_variation;
+            playback.pitch *= 1.0 + rand::thread_rng().gen_range(-variation..variation);
+        }
+        self.backend.play(self.tick, name, clip, &playback);
         Ok(())
     }
 
-    pub fn wait(&self) {
-        self.audio.wait();
+    /// Starts looping `source` as the background music, see `music::Music`
+    pub fn play_music(&mut self, name: &str, source: MusicSource) {
+        let volume = self.mixer.gain(Category::Music);
+        self.backend.play_music(self.tick, name, source, volume);
+    }
+
+    pub fn stop_music(&mut self) {
+        self.backend.stop_music();
+    }
+
+    /// Blocks until every sound and the music have finished, or at most
+    /// for `limit`
+    pub fn wait(&self, limit: Duration) {
+        let start = Instant::now();
+        while self.backend.busy() && start.elapsed() < limit {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl Default for Sound {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Turns game events into sound effects, and switches to the game over
+/// music when it's all over
+pub struct SoundEffects {
+    pub sound: Sound,
+    pub music: Music,
+    march_beat: usize,
+}
+
+impl SoundEffects {
+    pub fn new(sound: Sound, music: Music) -> Self {
+        Self {
+            sound,
+            music,
+            march_beat: 0,
+        }
+    }
+
+    pub fn set_scene(&mut self, scene: Scene) {
+        self.music.play(scene, &mut self.sound);
+    }
+}
+
+impl Subscriber for SoundEffects {
+    fn notify(&mut self, event: &Event) {
+        match *event {
+            Event::ShotFired { x, .. } => self.sound.play_with("pew", Playback::at(x)),
+            Event::InvaderKilled { x, .. } => self
+                .sound
+                .play_with("boom", Playback::at(x).with_priority(Priority::Low)),
+            // losing a life must be heard over everything else
+            Event::PlayerHit { x, .. } => self
+                .sound
+                .play_with("boom", Playback::at(x).with_priority(Priority::High)),
+            Event::ArmyMarched => {
+                self.sound.play_with(
+                    &format!("march{}", self.march_beat + 1),
+                    Playback::default().with_priority(Priority::Low),
+                );
+                self.march_beat = (self.march_beat + 1) % synth::MARCH_BEATS;
+            }
+            Event::GameOver(_) => self.set_scene(Scene::GameOver),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bot::Action, game::Game};
+
+    fn recording() -> SoundEffects {
+        let mut sound = Sound::with_backend(Box::new(NullBackend::recording()));
+        sound.add_defaults().unwrap();
+        SoundEffects::new(sound, Music::silent())
+    }
+
+    fn played(effects: &SoundEffects) -> Vec<(u64, &str)> {
+        let played = effects.sound.backend().played();
+        played
+            .iter()
+            .map(|(tick, name)| (*tick, name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn records_events_on_their_tick() {
+        let mut effects = recording();
+        effects.notify(&Event::ShotFired { x: 10, y: 20 });
+        effects.sound.next_tick();
+        effects.notify(&Event::ArmyMarched);
+        effects.notify(&Event::InvaderKilled {
+            kind: 0,
+            x: 10,
+            y: 5,
+            score: 10,
+        });
+        // still cooling down from the last one
+        effects.notify(&Event::InvaderKilled {
+            kind: 0,
+            x: 12,
+            y: 5,
+            score: 10,
+        });
+        effects.sound.next_tick();
+        effects.notify(&Event::ArmyMarched);
+
+        assert_eq!(
+            played(&effects),
+            [(0, "pew"), (1, "march1"), (1, "boom"), (2, "march2")]
+        );
+    }
+
+    #[test]
+    fn records_a_scripted_game() {
+        let mut effects = recording();
+        for (name, _) in assets::SFX {
+            effects.sound.set_cooldown(name, Duration::ZERO);
+        }
+
+        let mut game = Game::with_seed(1);
+        for tick in 0..500 {
+            effects.sound.next_tick();
+            if tick % 100 == 0 {
+                game.act(Action::Fire);
+            }
+            game.update(Duration::from_millis(10));
+            game.events.publish(&mut [&mut effects]);
+        }
+
+        assert_eq!(
+            played(&effects),
+            [
+                (1, "pew"),
+                (40, "boom"),
+                (101, "pew"),
+                (101, "march1"),
+                (164, "boom"),
+                (201, "pew"),
+                (202, "march2"),
+                (288, "boom"),
+                (301, "pew"),
+                (303, "march3"),
+                (401, "pew"),
+                (404, "march4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_records_when_asked() {
+        let mut sound = Sound::silent();
+        sound.add_defaults().unwrap();
+        sound.play("pew");
+        assert!(sound.backend().played().is_empty());
     }
 }
@@ -0,0 +1,181 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{self, Event, KeyCode},
+    terminal, ExecutableCommand, QueueableCommand,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, error::TrySendError},
+    task::JoinHandle,
+};
+
+use crate::{
+    frame::{new_frame, Frame},
+    render::{self, RenderContext},
+};
+
+// How many frames a spectator may lag behind before it gets a full redraw instead
+const VIEWER_BACKLOG: usize = 32;
+
+struct Viewer {
+    tx: mpsc::Sender<Arc<Vec<u8>>>,
+    // set when frames were dropped for this viewer, so its screen is out of sync
+    stale: bool,
+}
+
+struct Shared {
+    last_frame: Frame,
+    viewers: Vec<Viewer>,
+    context: RenderContext,
+}
+
+/// Streams rendered frames to read-only spectators connected over TCP.
+///
+/// The stream is plain terminal escape codes, so `nc <addr>` works as a
+/// viewer as well as `--watch <addr>`. Dropping it hangs up on everyone.
+pub struct Broadcaster {
+    shared: Arc<Mutex<Shared>>,
+    accept: JoinHandle<()>,
+}
+
+impl Broadcaster {
+    /// Frames are rendered for spectators the way `context` says
+    pub async fn bind(addr: &str, context: RenderContext) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let shared = Arc::new(Mutex::new(Shared {
+            last_frame: new_frame(),
+            viewers: Vec::new(),
+            context,
+        }));
+
+        let accept_shared = shared.clone();
+        let accept = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (tx, rx) = mpsc::channel(VIEWER_BACKLOG);
+
+                let mut shared = accept_shared.lock().unwrap();
+                // late joiners get the whole screen first
+                let _ = tx.try_send(Arc::new(full_redraw(&shared.last_frame, &shared.context)));
+                shared.viewers.push(Viewer { tx, stale: false });
+
+                tokio::spawn(serve_viewer(stream, rx));
+            }
+        });
+
+        Ok(Self { shared, accept })
+    }
+
+    pub fn viewers(&self) -> usize {
+        self.shared.lock().unwrap().viewers.len()
+    }
+
+    pub fn publish(&self, frame: &Frame) {
+        let mut shared = self.shared.lock().unwrap();
+        let Shared {
+            last_frame,
+            viewers,
+            context,
+        } = &mut *shared;
+
+        let mut diff = None;
+        let mut full = None;
+
+        viewers.retain_mut(|viewer| {
+            let bytes = if viewer.stale {
+                full.get_or_insert_with(|| Arc::new(full_redraw(frame, context)))
+            } else {
+                diff.get_or_insert_with(|| {
+                    let mut buf = Vec::new();
+                    render::render(&mut buf, last_frame, frame, false, context);
+                    Arc::new(buf)
+                })
+            };
+
+            match viewer.tx.try_send(bytes.clone()) {
+                Ok(_) => {
+                    viewer.stale = false;
+                    true
+                }
+                Err(TrySendError::Full(_)) => {
+                    viewer.stale = true;
+                    true
+                }
+                Err(TrySendError::Closed(_)) => false,
+            }
+        });
+
+        *last_frame = frame.clone();
+    }
+}
+
+impl Drop for Broadcaster {
+    fn drop(&mut self) {
+        self.accept.abort();
+        // viewers see the end of the stream once they've had what was sent
+        self.shared.lock().unwrap().viewers.clear();
+    }
+}
+
+fn full_redraw(frame: &Frame, context: &RenderContext) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.queue(Hide).unwrap();
+    render::render(&mut buf, frame, frame, true, context);
+    buf
+}
+
+async fn serve_viewer(mut stream: TcpStream, mut rx: mpsc::Receiver<Arc<Vec<u8>>>) {
+    while let Some(bytes) = rx.recv().await {
+        if stream.write_all(&bytes).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Attach to a broadcasting game and mirror it in this terminal until the
+/// game ends or `q`/`Esc` is pressed.
+pub async fn watch(addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stream = TcpStream::connect(addr).await?;
+
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    stdout.execute(terminal::EnterAlternateScreen)?;
+    stdout.execute(Hide)?;
+
+    let mut buf = [0u8; 4096];
+    let result: Result<(), Box<dyn std::error::Error>> = async {
+        'watch: loop {
+            while event::poll(Duration::default())? {
+                if let Event::Key(key_event) = event::read()? {
+                    if let KeyCode::Char('q') | KeyCode::Esc = key_event.code {
+                        break 'watch;
+                    }
+                }
+            }
+
+            match tokio::time::timeout(Duration::from_millis(50), stream.read(&mut buf)).await {
+                Ok(Ok(0)) => break,
+                Ok(Ok(n)) => {
+                    io::Write::write_all(&mut stdout, &buf[..n])?;
+                    io::Write::flush(&mut stdout)?;
+                }
+                Ok(Err(e)) => return Err(e.into()),
+                Err(_) => {}
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    stdout.execute(Show)?;
+    stdout.execute(terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+
+    result
+}
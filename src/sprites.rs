@@ -0,0 +1,409 @@
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use crossterm::style::Color;
+
+use crate::{assets::parse_word, frame::Frame};
+
+/// The sprites the game ships with
+pub const DEFAULT_SHEET: &str = include_str!("../assets/sprites/default.sprites");
+
+// Frames last this long unless the sheet says otherwise
+const DEFAULT_DURATION_MS: u64 = 500;
+
+const KEYWORDS: [&str; 6] = [
+    "sprite",
+    "frame",
+    "duration",
+    "color",
+    "transparent",
+    "hitbox",
+];
+
+#[derive(Clone)]
+pub struct SpriteFrame {
+    pub rows: Vec<Vec<char>>,
+    pub duration_ms: u64,
+    pub color: Option<Color>,
+}
+
+/// The part of a sprite that can be hit, relative to its top left corner
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hitbox {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Clone)]
+pub struct Sprite {
+    pub frames: Vec<SpriteFrame>,
+    /// Cells with this character aren't drawn
+    pub transparent: Option<char>,
+    hitbox: Option<Hitbox>,
+    width: usize,
+    height: usize,
+}
+
+impl Sprite {
+    /// Stands in for a sprite a sheet doesn't have
+    pub fn placeholder() -> Self {
+        Self {
+            frames: vec![SpriteFrame {
+                rows: vec![vec!['?']],
+                duration_ms: DEFAULT_DURATION_MS,
+                color: None,
+            }],
+            transparent: None,
+            hitbox: None,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    /// The widest row of any frame
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn duration_ms(&self, frame: usize) -> u64 {
+        self.frames
+            .get(frame)
+            .map_or(DEFAULT_DURATION_MS, |frame| frame.duration_ms)
+    }
+
+    pub fn hitbox(&self) -> Hitbox {
+        self.hitbox.unwrap_or(Hitbox {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        })
+    }
+
+    /// Draws one frame with its top left corner at `x`, `y`. Spaces leave
+    /// whatever is underneath alone and anything off screen is cut off.
+    pub fn draw(&self, frame: &mut Frame, x: usize, y: usize, index: usize) {
+        let Some(sprite_frame) = self.frames.get(index % self.frames.len().max(1)) else {
+            return;
+        };
+
+        for (dy, row) in sprite_frame.rows.iter().enumerate() {
+            for (dx, &c) in row.iter().enumerate() {
+                if c == ' ' || Some(c) == self.transparent {
+                    continue;
+                }
+                let (fx, fy) = (x + dx, y + dy);
+                if fx < frame.len() && fy < frame[fx].len() {
+                    frame.set(fx, fy, c, sprite_frame.color);
+                }
+            }
+        }
+    }
+}
+
+/// Named sprites, parsed from the format in `assets/sprites/default.sprites`.
+/// Lines starting with `#` are comments, except in a frame:
+///
+/// ```text
+/// sprite invader0      # starts a sprite, the name is what the game asks for
+/// duration 500         # milliseconds per frame
+/// color red            # a colour name or #rrggbb
+/// transparent .        # cells with this character aren't drawn, like spaces
+/// hitbox 0 0 5 2       # x y width height, the whole sprite if left out
+/// frame 300 yellow     # a frame, optionally with its own duration and colour
+///  /^\
+/// |^|^|
+/// ```
+#[derive(Clone, Default)]
+pub struct SpriteSheet {
+    sprites: HashMap<String, Arc<Sprite>>,
+}
+
+impl SpriteSheet {
+    /// The sprites compiled into the binary
+    pub fn builtin() -> Self {
+        Self::parse(DEFAULT_SHEET).expect("built-in sprite sheet is valid")
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut sheet = Self::default();
+        let mut current: Option<(String, Sprite, u64, Option<Color>)> = None;
+        let mut in_frame = false;
+
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: &str| format!("Line {}: {}", number + 1, message);
+            // a `#` on its own ends a keyword line, the rest is a comment
+            let mut words = line.split_whitespace().take_while(|word| *word != "#");
+            let keyword = words
+                .next()
+                .filter(|word| KEYWORDS.contains(word) && line.starts_with(word));
+
+            // frame rows are taken as they are, `#` and all
+            if (!in_frame && line.starts_with('#'))
+                || (in_frame && keyword.is_none() && line.trim().is_empty())
+            {
+                in_frame = false;
+                continue;
+            }
+
+            if in_frame && keyword.is_none() {
+                let (_, sprite, _, _) = current.as_mut().ok_or_else(|| error("no sprite"))?;
+                if let Some(frame) = sprite.frames.last_mut() {
+                    frame.rows.push(line.chars().collect());
+                }
+                continue;
+            }
+            in_frame = false;
+
+            let Some(keyword) = keyword else {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                return Err(error("expected a keyword").into());
+            };
+
+            if keyword == "sprite" {
+                if let Some((name, sprite, _, _)) = current.take() {
+                    sheet.insert(name, sprite);
+                }
+                let name = words.next().ok_or_else(|| error("sprite needs a name"))?;
+                let sprite = Sprite {
+                    frames: Vec::new(),
+                    transparent: None,
+                    hitbox: None,
+                    width: 0,
+                    height: 0,
+                };
+                current = Some((name.to_string(), sprite, DEFAULT_DURATION_MS, None));
+                continue;
+            }
+
+            let (_, sprite, duration, color) = current
+                .as_mut()
+                .ok_or_else(|| error("expected a sprite first"))?;
+
+            match keyword {
+                "duration" => {
+                    *duration = parse_word(words.next()).ok_or_else(|| error("bad duration"))?
+                }
+                "color" => {
+                    let name = words.next().ok_or_else(|| error("missing colour"))?;
+                    *color = Some(parse_color(name).ok_or_else(|| error("unknown colour"))?);
+                }
+                "transparent" => {
+                    sprite.transparent = words.next().and_then(|word| word.chars().next())
+                }
+                "hitbox" => {
+                    let numbers = words
+                        .map(|word| word.parse().ok())
+                        .collect::<Option<Vec<usize>>>()
+                        .ok_or_else(|| error("bad hitbox"))?;
+                    let [x, y, width, height] = numbers[..] else {
+                        return Err(error("hitbox needs x y width height").into());
+                    };
+                    sprite.hitbox = Some(Hitbox {
+                        x,
+                        y,
+                        width,
+                        height,
+                    });
+                }
+                "frame" => {
+                    let mut frame = SpriteFrame {
+                        rows: Vec::new(),
+                        duration_ms: *duration,
+                        color: *color,
+                    };
+                    for word in words {
+                        match word.parse() {
+                            Ok(ms) => frame.duration_ms = ms,
+                            Err(_) => {
+                                frame.color =
+                                    Some(parse_color(word).ok_or_else(|| error("unknown colour"))?)
+                            }
+                        }
+                    }
+                    sprite.frames.push(frame);
+                    in_frame = true;
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        if let Some((name, sprite, _, _)) = current.take() {
+            sheet.insert(name, sprite);
+        }
+        Ok(sheet)
+    }
+
+    fn insert(&mut self, name: String, mut sprite: Sprite) {
+        if sprite.frames.is_empty() {
+            return;
+        }
+        // trailing blank rows are just spacing between frames
+        for frame in &mut sprite.frames {
+            while frame
+                .rows
+                .last()
+                .is_some_and(|row| row.iter().all(|c| *c == ' '))
+            {
+                frame.rows.pop();
+            }
+        }
+        sprite.width = sprite
+            .frames
+            .iter()
+            .flat_map(|frame| frame.rows.iter().map(Vec::len))
+            .max()
+            .unwrap_or(0);
+        sprite.height = sprite
+            .frames
+            .iter()
+            .map(|frame| frame.rows.len())
+            .max()
+            .unwrap_or(0);
+        self.sprites.insert(name, Arc::new(sprite));
+    }
+
+    /// Takes over every sprite `other` has
+    pub fn merge(&mut self, other: SpriteSheet) {
+        self.sprites.extend(other.sprites);
+    }
+
+    /// Loads every `.sprites` file in `dir` on top of this sheet
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), Box<dyn std::error::Error>> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        // later files win, so make it the same order everywhere
+        paths.sort();
+
+        for path in paths {
+            if path.extension().is_some_and(|ext| ext == "sprites") {
+                self.merge(Self::load(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws every frame of the sprite called `name` in `color`
+    pub fn recolor(&mut self, name: &str, color: Color) {
+        if let Some(sprite) = self.sprites.get_mut(name) {
+            for frame in &mut Arc::make_mut(sprite).frames {
+                frame.color = Some(color);
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Arc<Sprite> {
+        self.sprites
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| Arc::new(Sprite::placeholder()))
+    }
+}
+
+/// A colour name like `red` or `dark_red`, or `#rrggbb`
+pub fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |at: usize| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+
+    Some(match name {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        "dark_grey" | "dark_gray" => Color::DarkGrey,
+        "dark_red" => Color::DarkRed,
+        "dark_green" => Color::DarkGreen,
+        "dark_yellow" => Color::DarkYellow,
+        "dark_blue" => Color::DarkBlue,
+        "dark_magenta" => Color::DarkMagenta,
+        "dark_cyan" => Color::DarkCyan,
+        "default" => Color::Reset,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_rows_can_start_with_a_hash() {
+        let sheet = SpriteSheet::parse(
+            "# a solid block\nsprite block\nframe\n#####\n#####\n\n# not part of it\n",
+        )
+        .unwrap();
+        let block = sheet.get("block");
+        assert_eq!((block.width(), block.height()), (5, 2));
+        assert_eq!(block.frames[0].rows[0], vec!['#'; 5]);
+    }
+
+    #[test]
+    fn keyword_lines_can_end_in_a_comment() {
+        let sheet = SpriteSheet::parse(
+            "sprite invader0      # starts a sprite\n\
+             duration 500         # milliseconds per frame\n\
+             color #ff0000        # a colour name or #rrggbb\n\
+             transparent .        # cells with this character aren't drawn\n\
+             hitbox 0 0 5 2       # x y width height\n\
+             frame 300 yellow     # a frame\n\
+             \x20/^\\\n\
+             |^|^|\n",
+        )
+        .unwrap();
+        let sprite = sheet.get("invader0");
+        assert_eq!(
+            sprite.hitbox(),
+            Hitbox {
+                x: 0,
+                y: 0,
+                width: 5,
+                height: 2
+            }
+        );
+        assert_eq!(sprite.transparent, Some('.'));
+        assert_eq!(sprite.duration_ms(0), 300);
+        assert_eq!(sprite.frames[0].color, Some(Color::Yellow));
+        assert_eq!(sprite.frames[0].rows.len(), 2);
+    }
+
+    #[test]
+    fn the_builtin_sheet_parses() {
+        let sheet = SpriteSheet::builtin();
+        assert_eq!(sheet.get("player").width(), 7);
+        assert_eq!(sheet.get("invader0").frames.len(), 2);
+    }
+}
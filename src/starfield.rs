@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use crossterm::style::Color;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    frame::{Drawable, Frame, Updatable},
+    NUM_COLS, NUM_ROWS,
+};
+
+// The top row belongs to the HUD
+const FIRST_ROW: usize = 1;
+
+// Every star blinks out for a moment once in a while
+const TWINKLE_PERIOD_MS: std::ops::Range<u64> = 1500..4000;
+const TWINKLE_OFF_MS: u64 = 120;
+
+/// Stars that are equally far away
+struct Depth {
+    count: usize,
+    rows_per_second: f64,
+    glyph: char,
+    color: Color,
+}
+
+// far to near, the near ones are drawn last and move fastest
+const DEPTHS: [Depth; 3] = [
+    Depth {
+        count: 30,
+        rows_per_second: 0.25,
+        glyph: '.',
+        color: Color::DarkGrey,
+    },
+    Depth {
+        count: 18,
+        rows_per_second: 0.5,
+        glyph: '.',
+        color: Color::Grey,
+    },
+    Depth {
+        count: 8,
+        rows_per_second: 1.0,
+        glyph: '*',
+        color: Color::White,
+    },
+];
+
+struct Star {
+    x: usize,
+    /// Where it was when the starfield was made
+    y: f64,
+    depth: usize,
+    twinkle_period_ms: u64,
+    twinkle_phase_ms: u64,
+}
+
+/// Slowly falling stars behind the game, the closer ones falling faster.
+/// They're only for show, nothing in the game knows they are there.
+pub struct Starfield {
+    stars: Vec<Star>,
+    elapsed: Duration,
+}
+
+impl Starfield {
+    /// The same seed gives the same sky
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut stars = Vec::new();
+        for (depth, layer) in DEPTHS.iter().enumerate() {
+            for _ in 0..layer.count {
+                let twinkle_period_ms = rng.gen_range(TWINKLE_PERIOD_MS);
+                stars.push(Star {
+                    x: rng.gen_range(0..NUM_COLS),
+                    y: rng.gen_range(0.0..(NUM_ROWS - FIRST_ROW) as f64),
+                    depth,
+                    twinkle_period_ms,
+                    twinkle_phase_ms: rng.gen_range(0..twinkle_period_ms),
+                });
+            }
+        }
+
+        Self {
+            stars,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl Updatable for Starfield {
+    fn update(&mut self, delta: Duration) {
+        self.elapsed += delta;
+    }
+}
+
+impl Drawable for Starfield {
+    fn draw(&self, frame: &mut Frame) {
+        let rows = (NUM_ROWS - FIRST_ROW) as f64;
+        let seconds = self.elapsed.as_secs_f64();
+        let elapsed_ms = self.elapsed.as_millis() as u64;
+
+        for star in &self.stars {
+            if (elapsed_ms + star.twinkle_phase_ms) % star.twinkle_period_ms < TWINKLE_OFF_MS {
+                continue;
+            }
+            // worked out from the total time rather than moved a bit every
+            // frame, so rounding doesn't add up
+            let depth = &DEPTHS[star.depth];
+            let y = (star.y + depth.rows_per_second * seconds) % rows;
+            frame.set(
+                star.x,
+                FIRST_ROW + y as usize,
+                depth.glyph,
+                Some(depth.color),
+            );
+        }
+    }
+}
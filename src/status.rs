@@ -1,4 +1,14 @@
-use crate::frame::{Drawable, Frame};
+use crate::{
+    frame::{Drawable, Frame},
+    NUM_COLS,
+};
+
+/// The invader commander's side of the HUD in versus mode
+pub struct Versus {
+    pub hits: usize,
+    pub column: usize,
+    pub march_nudge: i64,
+}
 
 pub struct Status {
     pub score: usize,
@@ -7,6 +17,7 @@ pub struct Status {
     pub paused: bool,
     pub game_over: bool,
     pub game_won: bool,
+    pub versus: Option<Versus>,
 }
 
 impl Status {
@@ -18,9 +29,18 @@ impl Status {
             paused: false,
             game_over: false,
             game_won: false,
+            versus: None,
         }
     }
 
+    pub fn enable_versus(&mut self) {
+        self.versus = Some(Versus {
+            hits: 0,
+            column: 0,
+            march_nudge: 0,
+        });
+    }
+
     pub fn level_up(&mut self) {
         self.level += 1;
     }
@@ -34,6 +54,12 @@ impl Status {
     }
 }
 
+impl Default for Status {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Drawable for Status {
     fn draw(&self, frame: &mut Frame) {
         frame[0][0] = 'S';
@@ -69,5 +95,19 @@ impl Drawable for Status {
         frame[32][0] = ' ';
         frame[33][0] = ((self.level / 10) as u8 + 48) as char;
         frame[34][0] = ((self.level % 10) as u8 + 48) as char;
+
+        if let Some(versus) = &self.versus {
+            let text = format!(
+                "| Hits: {:02} Col: {} March: {:+04}",
+                versus.hits,
+                versus.column + 1,
+                versus.march_nudge
+            );
+            // right align the commander's half of the HUD
+            let start = NUM_COLS.saturating_sub(text.chars().count());
+            for (i, c) in text.chars().enumerate() {
+                frame[start + i][0] = c;
+            }
+        }
     }
 }
@@ -0,0 +1,142 @@
+use std::{f32::consts::TAU, fs, io, path::Path};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// All built-in effects are mono at this rate
+pub const SAMPLE_RATE: u32 = 22050;
+
+/// Number of notes in the invader march loop, named `march1` to `march4`
+pub const MARCH_BEATS: usize = 4;
+
+/// Every built-in effect with the name `Sound::play` knows it by
+pub fn effects() -> Vec<(String, Vec<i16>)> {
+    let mut effects = vec![
+        ("pew".to_string(), pew()),
+        ("boom".to_string(), boom()),
+        ("startup".to_string(), startup()),
+        ("ufo".to_string(), ufo()),
+    ];
+
+    // the classic descending four note bass line
+    let notes = [110.0, 98.0, 87.3, 82.4];
+    for (i, freq) in notes.iter().enumerate().take(MARCH_BEATS) {
+        effects.push((format!("march{}", i + 1), march(*freq)));
+    }
+
+    effects
+}
+
+fn samples(seconds: f32) -> usize {
+    (seconds * SAMPLE_RATE as f32) as usize
+}
+
+fn to_pcm(wave: impl Iterator<Item = f32>) -> Vec<i16> {
+    wave.map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+        .collect()
+}
+
+fn square(phase: f32) -> f32 {
+    if phase.fract() < 0.5 {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+/// A square wave sweeping down fast
+pub fn pew() -> Vec<i16> {
+    let len = samples(0.15);
+    let mut phase = 0.0;
+    to_pcm((0..len).map(|i| {
+        let t = i as f32 / len as f32;
+        let freq = 1200.0 - 900.0 * t;
+        phase += freq / SAMPLE_RATE as f32;
+        square(phase) * 0.3 * (1.0 - t)
+    }))
+}
+
+/// Filtered noise with a long decay
+pub fn boom() -> Vec<i16> {
+    let len = samples(0.5);
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut low = 0.0;
+    to_pcm((0..len).map(|i| {
+        let t = i as f32 / len as f32;
+        let noise: f32 = rng.gen_range(-1.0..1.0);
+        // one pole low pass that closes as the explosion fades
+        let cutoff = 0.5 * (1.0 - t) + 0.02;
+        low += (noise - low) * cutoff;
+        low * 0.8 * (1.0 - t).powi(2)
+    }))
+}
+
+/// A rising arpeggio
+pub fn startup() -> Vec<i16> {
+    let notes = [261.6, 329.6, 392.0, 523.3, 659.3, 784.0];
+    let note_len = samples(0.12);
+    let mut phase = 0.0;
+    to_pcm((0..note_len * notes.len()).map(|i| {
+        let freq = notes[i / note_len];
+        let t = (i % note_len) as f32 / note_len as f32;
+        phase += freq / SAMPLE_RATE as f32;
+        square(phase) * 0.2 * (1.0 - t * 0.7)
+    }))
+}
+
+/// A short low thump for one step of the march
+pub fn march(freq: f32) -> Vec<i16> {
+    let len = samples(0.1);
+    to_pcm((0..len).map(|i| {
+        let t = i as f32 / len as f32;
+        let time = i as f32 / SAMPLE_RATE as f32;
+        (TAU * freq * time).sin() * 0.6 * (1.0 - t)
+    }))
+}
+
+/// A sine with a fast vibrato, the flying saucer
+pub fn ufo() -> Vec<i16> {
+    let len = samples(1.0);
+    let mut phase = 0.0;
+    to_pcm((0..len).map(|i| {
+        let time = i as f32 / SAMPLE_RATE as f32;
+        let freq = 700.0 + 200.0 * (TAU * 8.0 * time).sin();
+        phase += freq / SAMPLE_RATE as f32;
+        (TAU * phase).sin() * 0.25
+    }))
+}
+
+/// Encodes mono samples as a 16 bit PCM WAV file
+pub fn wav(samples: &[i16]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // byte rate
+    bytes.extend_from_slice(&2u16.to_le_bytes()); // block align
+    bytes.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    bytes
+}
+
+/// Writes every built-in effect to `dir` as `<name>.wav`
+pub fn export<P: AsRef<Path>>(dir: P) -> io::Result<()> {
+    fs::create_dir_all(dir.as_ref())?;
+    for (name, samples) in effects() {
+        fs::write(dir.as_ref().join(format!("{}.wav", name)), wav(&samples))?;
+    }
+    Ok(())
+}
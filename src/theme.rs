@@ -0,0 +1,146 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crossterm::style::Color;
+
+use crate::{
+    assets,
+    sprites::{parse_color, SpriteSheet},
+};
+
+/// The themes compiled into the binary, as `theme.conf` and sprite sheet
+pub const BUILTIN: [(&str, &str, &str); 4] = [
+    (
+        "classic",
+        include_str!("../assets/themes/classic/theme.conf"),
+        "",
+    ),
+    ("neon", include_str!("../assets/themes/neon/theme.conf"), ""),
+    (
+        "monochrome",
+        include_str!("../assets/themes/monochrome/theme.conf"),
+        "",
+    ),
+    (
+        "holiday",
+        include_str!("../assets/themes/holiday/theme.conf"),
+        include_str!("../assets/themes/holiday/holiday.sprites"),
+    ),
+];
+
+/// The look and sound of the game. A theme directory holds a `theme.conf`
+/// with `key = value` lines, any number of `.sprites` files and optionally
+/// an `sfx` directory with sounds replacing the defaults:
+///
+/// ```text
+/// name = Neon
+/// # around the playing field
+/// border = #1a0033
+/// background = black
+/// # anything drawn without a colour of its own
+/// text = #ff6ec7
+/// # every frame of the `player` sprite
+/// color.player = cyan
+/// ```
+pub struct Theme {
+    pub name: String,
+    pub border: Color,
+    pub background: Color,
+    pub text: Option<Color>,
+    /// Colours for whole sprites, by sprite name
+    pub palette: Vec<(String, Color)>,
+    /// Sprites replacing the built-in ones
+    pub sprites: SpriteSheet,
+    pub sfx: Option<PathBuf>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: "Classic".to_string(),
+            border: Color::Blue,
+            background: Color::Black,
+            text: None,
+            palette: Vec::new(),
+            sprites: SpriteSheet::default(),
+            sfx: None,
+        }
+    }
+}
+
+impl Theme {
+    /// A built-in theme by name, or a theme directory. Names that are
+    /// neither are looked up in the `themes` asset directories.
+    pub fn find(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some((_, conf, sprites)) = BUILTIN.iter().find(|(builtin, _, _)| *builtin == name) {
+            return Self::parse(conf, SpriteSheet::parse(sprites)?);
+        }
+
+        if Path::new(name).is_dir() {
+            return Self::load_dir(name);
+        }
+
+        for dir in assets::theme_dirs() {
+            if dir.join(name).is_dir() {
+                return Self::load_dir(dir.join(name));
+            }
+        }
+
+        Err(format!("Theme {} not found", name).into())
+    }
+
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = dir.as_ref();
+        let mut sprites = SpriteSheet::default();
+        sprites.load_dir(dir)?;
+
+        let mut theme = Self::parse(&fs::read_to_string(dir.join("theme.conf"))?, sprites)?;
+        if dir.join("sfx").is_dir() {
+            theme.sfx = Some(dir.join("sfx"));
+        }
+        Ok(theme)
+    }
+
+    pub fn parse(conf: &str, sprites: SpriteSheet) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut theme = Self {
+            sprites,
+            ..Self::default()
+        };
+
+        for (number, line) in conf.lines().enumerate() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let color =
+                || parse_color(value).ok_or_else(|| format!("Line {}: unknown colour", number + 1));
+
+            match key {
+                "name" => theme.name = value.to_string(),
+                "border" => theme.border = color()?,
+                "background" => theme.background = color()?,
+                "text" => theme.text = Some(color()?),
+                _ => {
+                    if let Some(sprite) = key.strip_prefix("color.") {
+                        theme.palette.push((sprite.to_string(), color()?));
+                    }
+                }
+            }
+        }
+
+        Ok(theme)
+    }
+
+    /// Puts this theme's sprites and colours into `sheet`
+    pub fn apply(&self, sheet: &mut SpriteSheet) {
+        sheet.merge(self.sprites.clone());
+        for (sprite, color) in &self.palette {
+            sheet.recolor(sprite, *color);
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use crate::{
+    frame::{Drawable, Frame},
+    NUM_COLS,
+};
+
+const LINES: [&str; 3] = ["I N V A D E R S", "", "Press any key to start, q to quit"];
+// First line, under the army and clear of the cannon
+const TOP: usize = 17;
+
+/// What's shown over the playfield while the title tune plays, until the
+/// player is ready
+pub struct Title;
+
+impl Drawable for Title {
+    fn draw(&self, frame: &mut Frame) {
+        for (y, line) in LINES.iter().enumerate() {
+            let left = (NUM_COLS - line.len()) / 2;
+            for (x, c) in line.chars().enumerate() {
+                frame[left + x][TOP + y] = c;
+            }
+        }
+    }
+}